@@ -0,0 +1,84 @@
+use std::{
+	io::{self, Write},
+	process::{Command, Stdio},
+};
+
+use git2::Config;
+
+pub enum Clipboard {
+	// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Operating-System-Commands
+	// works over ssh as long as the terminal (and tmux, with `set-clipboard on`) supports it
+	Osc52,
+	Command(String),
+}
+
+impl Clipboard {
+	// `gil.clipboard` is either `osc52` (the default) or a command that reads from stdin, like `wl-copy` or
+	// `xclip -selection clipboard`
+	pub fn from_config(config: &Config) -> Result<Clipboard, String> {
+		match config.get_string("gil.clipboard") {
+			Ok(value) if value == "osc52" => Ok(Clipboard::Osc52),
+			Ok(command) => Ok(Clipboard::Command(command)),
+			Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(Clipboard::Osc52),
+			Err(err) => Err(err.message().to_owned()),
+		}
+	}
+
+	// `term` is where the TUI is drawn, which isn't stdout in --pick mode
+	pub fn copy(&self, text: &str, term: &mut dyn Write) -> io::Result<()> {
+		match self {
+			Clipboard::Osc52 => {
+				write!(term, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+				term.flush()
+			},
+			Clipboard::Command(command) => {
+				let mut proc = Command::new("sh")
+					.args(["-c", command])
+					.stdin(Stdio::piped())
+					.stdout(Stdio::null())
+					.stderr(Stdio::null())
+					.spawn()?;
+				proc.stdin.take().unwrap().write_all(text.as_bytes())?;
+				let status = proc.wait()?;
+				if !status.success() {
+					return Err(io::Error::other(format!("`{}` failed: {}", command, status)));
+				}
+				Ok(())
+			},
+		}
+	}
+}
+
+fn base64(input: &[u8]) -> String {
+	const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+	for chunk in input.chunks(3) {
+		let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+		for i in 0..4 {
+			if i <= chunk.len() {
+				out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+			} else {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn base64_padding() {
+		assert_eq!(base64(b""), "");
+		assert_eq!(base64(b"f"), "Zg==");
+		assert_eq!(base64(b"fo"), "Zm8=");
+		assert_eq!(base64(b"foo"), "Zm9v");
+		assert_eq!(base64(b"foob"), "Zm9vYg==");
+		assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+		assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+		assert_eq!(base64(&[0xff, 0xfe, 0x00]), "//4A");
+	}
+}
@@ -0,0 +1,202 @@
+use std::{
+	error::Error,
+	fmt::Write as _,
+	io::{self, Write},
+};
+
+use git2::{BranchType, Repository, Revwalk};
+
+use crate::git::{file_stats, next_matching_commit, CommitInfo, Decorations, FileStat, LogFilter, Pickaxe};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+	Json,   // one array of commits
+	Ndjson, // one commit object per line
+	Csv,    // one row per commit
+}
+
+impl Format {
+	pub fn from_name(name: &str) -> Option<Format> {
+		match name {
+			"json" => Some(Format::Json),
+			"ndjson" => Some(Format::Ndjson),
+			"csv" => Some(Format::Csv),
+			_ => None,
+		}
+	}
+}
+
+const CSV_HEADER: &str = "id,author_name,author_email,time,summary,message,files,insertions,deletions,branches,tags";
+
+pub fn export(
+	repo: &Repository,
+	revwalk: &mut Revwalk,
+	decorations: &Decorations,
+	format: Format,
+	pickaxe: Option<&Pickaxe>,
+) -> Result<(), Box<dyn Error>> {
+	let mut stdout = io::stdout().lock();
+	match format {
+		Format::Json => stdout.write_all(b"[")?,
+		Format::Ndjson => {},
+		Format::Csv => writeln!(stdout, "{}", CSV_HEADER)?,
+	}
+	let mut first = true;
+	while let Some(ci) = next_matching_commit(repo, revwalk, &LogFilter::default(), pickaxe)? {
+		let files = file_stats(&ci.patch)?;
+		match format {
+			Format::Json => {
+				if !first {
+					stdout.write_all(b",")?;
+				}
+				write!(stdout, "\n\t{}", json_commit(&ci, &files, decorations))?;
+			},
+			Format::Ndjson => writeln!(stdout, "{}", json_commit(&ci, &files, decorations))?,
+			Format::Csv => writeln!(stdout, "{}", csv_commit(&ci, &files, decorations))?,
+		}
+		first = false;
+	}
+	if format == Format::Json {
+		stdout.write_all(if first { b"]\n" } else { b"\n]\n" })?;
+	}
+	stdout.flush()?;
+	Ok(())
+}
+
+fn rfc3339(time: git2::Time) -> String {
+	let offset =
+		chrono::FixedOffset::east_opt(time.offset_minutes() * 60).unwrap_or(chrono::FixedOffset::east_opt(0).unwrap());
+	match chrono::DateTime::from_timestamp(time.seconds(), 0) {
+		Some(dt) => dt.with_timezone(&offset).to_rfc3339(),
+		None => "".to_string(),
+	}
+}
+
+fn branch_names(ci: &CommitInfo, decorations: &Decorations) -> Vec<String> {
+	match decorations.branches.get(&ci.commit_id) {
+		Some(branches) => branches
+			.iter()
+			.map(|(name, branch_type)| match branch_type {
+				BranchType::Local => name.clone(),
+				BranchType::Remote => format!("remotes/{}", name),
+			})
+			.collect(),
+		None => vec![],
+	}
+}
+
+fn json_commit(ci: &CommitInfo, files: &[FileStat], decorations: &Decorations) -> String {
+	let mut out = String::new();
+	_ = write!(
+		out,
+		r#"{{"id":{},"author_name":{},"author_email":{},"time":{},"summary":{},"message":{},"files":["#,
+		json_string(&ci.commit_id.to_string()),
+		json_string(&ci.author_name),
+		json_string(&ci.author_email),
+		json_string(&rfc3339(ci.author_time)),
+		json_string(&ci.summary),
+		json_string(&ci.message),
+	);
+	for (i, file) in files.iter().enumerate() {
+		if i > 0 {
+			out.push(',');
+		}
+		_ = write!(
+			out,
+			r#"{{"path":{},"old_path":{},"status":{},"insertions":{},"deletions":{}}}"#,
+			json_string(&file.path),
+			file.old_path.as_deref().map_or("null".to_owned(), json_string),
+			json_string(&file.status.to_string()),
+			file.insertions,
+			file.deletions,
+		);
+	}
+	out.push_str(r#"],"branches":"#);
+	out.push_str(&json_array(&branch_names(ci, decorations)));
+	out.push_str(r#","tags":"#);
+	out.push_str(&json_array(
+		decorations.tags.get(&ci.commit_id).map_or(&[][..], |tags| tags),
+	));
+	out.push('}');
+	out
+}
+
+fn json_array(items: &[String]) -> String {
+	format!(
+		"[{}]",
+		items.iter().map(|s| json_string(s)).collect::<Vec<_>>().join(",")
+	)
+}
+
+fn json_string(s: &str) -> String {
+	let mut out = String::with_capacity(s.len() + 2);
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => _ = write!(out, "\\u{:04x}", c as u32),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
+
+fn csv_commit(ci: &CommitInfo, files: &[FileStat], decorations: &Decorations) -> String {
+	let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+	let tags = decorations.tags.get(&ci.commit_id).map_or(&[][..], |tags| tags);
+	[
+		ci.commit_id.to_string(),
+		ci.author_name.clone(),
+		ci.author_email.clone(),
+		rfc3339(ci.author_time),
+		ci.summary.clone(),
+		ci.message.clone(),
+		paths.join(";"),
+		files.iter().map(|f| f.insertions).sum::<usize>().to_string(),
+		files.iter().map(|f| f.deletions).sum::<usize>().to_string(),
+		branch_names(ci, decorations).join(";"),
+		tags.join(";"),
+	]
+	.iter()
+	.map(|field| csv_field(field))
+	.collect::<Vec<_>>()
+	.join(",")
+}
+
+// https://www.rfc-editor.org/rfc/rfc4180
+fn csv_field(field: &str) -> String {
+	if field.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_owned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn json_string_escapes() {
+		assert_eq!(json_string("plain"), r#""plain""#);
+		assert_eq!(json_string(r#"say "hi" \ bye"#), r#""say \"hi\" \\ bye""#);
+		assert_eq!(json_string("a\nb\r\tc"), r#""a\nb\r\tc""#);
+		assert_eq!(json_string("\u{0}\u{1b}\u{1f} "), r#""\u0000\u001b\u001f ""#);
+		assert_eq!(json_string("ünï\u{7f}"), "\"ünï\u{7f}\"");
+	}
+
+	#[test]
+	fn csv_field_quotes() {
+		assert_eq!(csv_field("plain"), "plain");
+		assert_eq!(csv_field(""), "");
+		assert_eq!(csv_field("a,b"), r#""a,b""#);
+		assert_eq!(csv_field(r#"say "hi""#), r#""say ""hi""""#);
+		assert_eq!(csv_field("line\nnext"), "\"line\nnext\"");
+		assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+	}
+}
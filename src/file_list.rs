@@ -0,0 +1,434 @@
+use std::{
+	cmp::Reverse,
+	collections::{BTreeMap, HashSet},
+};
+
+use tui::{
+	text::{Line, Span},
+	widgets::ListState,
+};
+
+use crate::{git::FileStat, theme::Theme};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FileSort {
+	Path,
+	Size, // most lines changed first
+}
+
+enum Row {
+	Dir {
+		path: String,  // with a trailing slash, e.g. src/bin/
+		label: String, // directories with nothing else in them are joined, e.g. bin/sub
+		depth: usize,
+		collapsed: bool,
+		insertions: usize,
+		deletions: usize,
+	},
+	File {
+		index: usize, // of the delta in the commit's patch
+		depth: usize,
+	},
+}
+
+#[derive(Default)]
+struct Dir {
+	dirs: BTreeMap<String, Dir>,
+	files: Vec<usize>,
+}
+
+impl Dir {
+	fn counts(&self, stats: &[FileStat]) -> (usize, usize) {
+		let mut insertions = 0;
+		let mut deletions = 0;
+		for &index in &self.files {
+			insertions += stats[index].insertions;
+			deletions += stats[index].deletions;
+		}
+		for dir in self.dirs.values() {
+			let (i, d) = dir.counts(stats);
+			insertions += i;
+			deletions += d;
+		}
+		(insertions, deletions)
+	}
+}
+
+// the files of a commit as a directory tree. rows are directories and files; only file rows stand for a delta
+pub struct FileList {
+	stats: Vec<FileStat>,
+	sort: FileSort,
+	filter: String,
+	collapsed: HashSet<String>, // directory paths, ignored while filtering
+	rows: Vec<Row>,
+	shown: Option<usize>, // the file in the patch pane, which stays there while a directory is selected
+	pub state: ListState,
+}
+
+impl FileList {
+	pub fn new(sort: FileSort) -> FileList {
+		FileList {
+			stats: vec![],
+			sort,
+			filter: String::new(),
+			collapsed: HashSet::new(),
+			rows: vec![],
+			shown: None,
+			state: ListState::default(),
+		}
+	}
+
+	// for a new patch of the same commit, the filter and collapsed directories stay
+	pub fn load(&mut self, stats: Vec<FileStat>) {
+		self.stats = stats;
+		self.shown = None;
+		self.state = ListState::default();
+		self.rebuild();
+	}
+
+	pub fn len(&self) -> usize {
+		self.rows.len()
+	}
+
+	pub fn filter(&self) -> &str {
+		&self.filter
+	}
+
+	// returns the selected file, which is the first one left if the one before was filtered out
+	pub fn set_filter(&mut self, filter: String) -> Option<usize> {
+		let selected = self.selected();
+		self.filter = filter;
+		self.rebuild();
+		match selected {
+			Some(index) if self.row_of(index).is_some() => self.state.select(self.row_of(index)),
+			_ => self.state.select(self.first_file().and_then(|index| self.row_of(index))),
+		}
+		self.selected()
+	}
+
+	pub fn toggle_sort(&mut self) -> FileSort {
+		self.sort = match self.sort {
+			FileSort::Path => FileSort::Size,
+			FileSort::Size => FileSort::Path,
+		};
+		self.reselect_after(|list| list.rebuild());
+		self.sort
+	}
+
+	pub fn title(&self) -> Option<String> {
+		let mut parts = vec![];
+		if !self.filter.is_empty() {
+			if self.rows.is_empty() {
+				parts.push(format!("no files match {}", self.filter));
+			} else {
+				parts.push(format!("files matching {}", self.filter));
+			}
+		}
+		if self.sort == FileSort::Size {
+			parts.push("biggest changes first".to_owned());
+		}
+		if parts.is_empty() {
+			None
+		} else {
+			Some(parts.join("  "))
+		}
+	}
+
+	pub fn selected(&self) -> Option<usize> {
+		match self.rows.get(self.state.selected()?)? {
+			Row::File { index, .. } => Some(*index),
+			Row::Dir { .. } => None,
+		}
+	}
+
+	// the file to act on: the selected one, or while a directory is selected, the one in the patch pane or else the next
+	pub fn current(&self) -> Option<usize> {
+		self.selected().or(self.shown).or_else(|| self.neighbour(true))
+	}
+
+	pub fn set_shown(&mut self, index: usize) {
+		self.shown = Some(index);
+	}
+
+	// expands the directories it's in. false if it's filtered out
+	pub fn select(&mut self, index: usize) -> bool {
+		if self.row_of(index).is_none() {
+			if let Some(stat) = self.stats.get(index) {
+				self.collapsed.retain(|dir| !stat.path.starts_with(dir.as_str()));
+				self.rebuild();
+			}
+		}
+		let row = self.row_of(index);
+		if row.is_some() {
+			self.state.select(row);
+		}
+		row.is_some()
+	}
+
+	// like select, but leaves collapsed directories alone. the file is what the patch pane shows either way
+	pub fn follow(&mut self, index: usize) {
+		self.shown = Some(index);
+		if let Some(row) = self.row_of(index) {
+			self.state.select(Some(row));
+		}
+	}
+
+	pub fn first_file(&self) -> Option<usize> {
+		self.rows.iter().find_map(|row| match row {
+			Row::File { index, .. } => Some(*index),
+			Row::Dir { .. } => None,
+		})
+	}
+
+	// the next or previous file row from the selected row, skipping directories
+	pub fn neighbour(&self, forward: bool) -> Option<usize> {
+		let selected = self.state.selected();
+		let mut files = self.rows.iter().enumerate().filter_map(|(row, r)| match r {
+			Row::File { index, .. } => Some((row, *index)),
+			Row::Dir { .. } => None,
+		});
+		let found = if forward {
+			files.find(|(row, _)| selected.is_none_or(|selected| *row > selected))
+		} else {
+			files.rev().find(|(row, _)| selected.is_none_or(|selected| *row < selected))
+		};
+		found.map(|(_, index)| index)
+	}
+
+	// selects the row, and opens or closes it if it's a directory. returns the file selected afterwards
+	pub fn click(&mut self, row: usize) -> Option<usize> {
+		if row >= self.rows.len() {
+			return None;
+		}
+		self.state.select(Some(row));
+		if let Row::Dir { .. } = self.rows[row] {
+			self.toggle_dir();
+		}
+		self.selected()
+	}
+
+	// opens or closes the selected directory, or the one the selected file is in
+	pub fn toggle_dir(&mut self) {
+		if !self.filter.is_empty() {
+			return;
+		}
+		let Some(selected) = self.state.selected() else {
+			return;
+		};
+		let dir = match self.rows.get(selected) {
+			Some(Row::Dir { path, .. }) => Some(path.clone()),
+			Some(Row::File { depth, .. }) => self.rows[..selected].iter().rev().find_map(|row| match row {
+				Row::Dir { path, depth: d, .. } if d + 1 == *depth => Some(path.clone()),
+				_ => None,
+			}),
+			None => None,
+		};
+		let Some(dir) = dir else {
+			return;
+		};
+		if !self.collapsed.remove(&dir) {
+			self.collapsed.insert(dir.clone());
+		}
+		self.rebuild();
+		self.state.select(self.row_of_dir(&dir));
+	}
+
+	// opens everything if anything is closed, otherwise closes every directory
+	pub fn toggle_all(&mut self) {
+		if !self.filter.is_empty() {
+			return;
+		}
+		self.reselect_after(|list| {
+			if list.collapsed.is_empty() {
+				for row in &list.rows {
+					if let Row::Dir { path, .. } = row {
+						list.collapsed.insert(path.clone());
+					}
+				}
+			} else {
+				list.collapsed.clear();
+			}
+			list.rebuild();
+		});
+	}
+
+	pub fn lines(&self, theme: &Theme) -> Vec<Line<'static>> {
+		self.rows
+			.iter()
+			.map(|row| match row {
+				Row::Dir {
+					label,
+					depth,
+					collapsed,
+					insertions,
+					deletions,
+					..
+				} => {
+					let mut spans = vec![Span::raw(format!(
+						"{}{} {}/",
+						"  ".repeat(*depth),
+						if *collapsed { '▸' } else { '▾' },
+						label
+					))];
+					spans.extend(count_spans(*insertions, *deletions, theme));
+					Line::from(spans)
+				},
+				Row::File { index, depth } => {
+					let stat = &self.stats[*index];
+					let status_style = match stat.status {
+						'A' => theme.insertions,
+						'D' => theme.deletions,
+						_ => Default::default(),
+					};
+					let mut name = file_name(&stat.path).to_owned();
+					if let Some(ref old_path) = stat.old_path {
+						// the old name alone if it's in the same directory
+						let same_dir = parent(old_path) == parent(&stat.path);
+						let old_name = if same_dir { file_name(old_path) } else { old_path };
+						name = format!("{} → {}", old_name, name);
+					}
+					let mut spans = vec![
+						Span::raw("  ".repeat(*depth)),
+						Span::styled(stat.status.to_string(), status_style),
+						Span::raw(format!(" {}", name)),
+					];
+					spans.extend(count_spans(stat.insertions, stat.deletions, theme));
+					Line::from(spans)
+				},
+			})
+			.collect()
+	}
+
+	fn rebuild(&mut self) {
+		let mut root = Dir::default();
+		for (index, stat) in self.stats.iter().enumerate() {
+			if !fuzzy_match(&self.filter, &stat.path) {
+				continue;
+			}
+			let mut dir = &mut root;
+			let mut parts: Vec<&str> = stat.path.split('/').collect();
+			parts.pop();
+			for part in parts {
+				dir = dir.dirs.entry(part.to_owned()).or_default();
+			}
+			dir.files.push(index);
+		}
+		let collapsed = if self.filter.is_empty() {
+			Some(&self.collapsed)
+		} else {
+			None
+		};
+		let mut rows = vec![];
+		add_rows(&mut rows, &root, "", 0, &self.stats, self.sort, collapsed);
+		self.rows = rows;
+	}
+
+	// keeps the same file selected, or the closed directory it's in, or failing that the same directory
+	fn reselect_after(&mut self, change: impl FnOnce(&mut FileList)) {
+		let file = self.selected().map(|index| self.stats[index].path.clone());
+		let dir = match self.state.selected().and_then(|row| self.rows.get(row)) {
+			Some(Row::Dir { path, .. }) => Some(path.clone()),
+			_ => None,
+		};
+		change(self);
+		let row = match (file, dir) {
+			(Some(path), _) => self.rows.iter().rposition(|row| match row {
+				Row::File { index, .. } => self.stats[*index].path == path,
+				Row::Dir { path: dir, .. } => path.starts_with(dir.as_str()),
+			}),
+			(None, Some(dir)) => self.row_of_dir(&dir),
+			(None, None) => None,
+		};
+		self.state.select(row.or(if self.rows.is_empty() { None } else { Some(0) }));
+	}
+
+	fn row_of(&self, index: usize) -> Option<usize> {
+		self.rows.iter().position(|row| matches!(row, Row::File { index: i, .. } if *i == index))
+	}
+
+	fn row_of_dir(&self, dir: &str) -> Option<usize> {
+		self.rows.iter().position(|row| matches!(row, Row::Dir { path, .. } if path == dir))
+	}
+}
+
+// directories before files, each sorted by name or by the lines changed
+fn add_rows(
+	rows: &mut Vec<Row>,
+	dir: &Dir,
+	prefix: &str,
+	depth: usize,
+	stats: &[FileStat],
+	sort: FileSort,
+	collapsed: Option<&HashSet<String>>,
+) {
+	let mut dirs: Vec<(&String, &Dir)> = dir.dirs.iter().collect();
+	if sort == FileSort::Size {
+		dirs.sort_by_key(|(_, dir)| {
+			let (insertions, deletions) = dir.counts(stats);
+			Reverse(insertions + deletions)
+		});
+	}
+	for (name, mut sub) in dirs {
+		let mut label = name.clone();
+		while sub.files.is_empty() && sub.dirs.len() == 1 {
+			let (child_name, child) = sub.dirs.iter().next().unwrap();
+			label = format!("{}/{}", label, child_name);
+			sub = child;
+		}
+		let path = format!("{}{}/", prefix, label);
+		let (insertions, deletions) = sub.counts(stats);
+		let is_collapsed = collapsed.is_some_and(|collapsed| collapsed.contains(&path));
+		rows.push(Row::Dir {
+			path: path.clone(),
+			label,
+			depth,
+			collapsed: is_collapsed,
+			insertions,
+			deletions,
+		});
+		if !is_collapsed {
+			add_rows(rows, sub, &path, depth + 1, stats, sort, collapsed);
+		}
+	}
+
+	let mut files = dir.files.clone();
+	match sort {
+		FileSort::Path => files.sort_by(|a, b| stats[*a].path.cmp(&stats[*b].path)),
+		FileSort::Size => files.sort_by_key(|index| Reverse(stats[*index].insertions + stats[*index].deletions)),
+	}
+	rows.extend(files.into_iter().map(|index| Row::File { index, depth }));
+}
+
+fn count_spans(insertions: usize, deletions: usize, theme: &Theme) -> Vec<Span<'static>> {
+	let mut spans = vec![];
+	if insertions > 0 {
+		spans.push(Span::styled(format!("  +{}", insertions), theme.insertions));
+	}
+	if deletions > 0 {
+		spans.push(Span::styled(format!("  −{}", deletions), theme.deletions));
+	}
+	spans
+}
+
+// the query's characters in order, anywhere in the path. case insensitive unless the query has an uppercase letter
+fn fuzzy_match(query: &str, path: &str) -> bool {
+	let case_sensitive = query.chars().any(char::is_uppercase);
+	let mut path_chars = path.chars();
+	query.chars().all(|q| {
+		path_chars.any(|c| {
+			if case_sensitive {
+				c == q
+			} else {
+				c.to_lowercase().eq(q.to_lowercase())
+			}
+		})
+	})
+}
+
+fn file_name(path: &str) -> &str {
+	path.rsplit('/').next().unwrap_or(path)
+}
+
+fn parent(path: &str) -> &str {
+	path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
@@ -1,29 +1,167 @@
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	ffi::OsString,
-	path::Path,
+	fs,
+	path::{Path, PathBuf},
 	process::{Command, Stdio},
 };
 
 use ansi_to_tui::IntoText;
-use git2::{BranchType, Diff, DiffStatsFormat, Oid, Repository, Revwalk};
-use tui::{
-	style::Stylize,
-	text::{Line, Span, Text},
+use git2::{
+	BranchType, Commit, Diff, DiffFindOptions, DiffFormat, DiffOptions, DiffStatsFormat, Email, EmailCreateOptions,
+	Oid, Patch, Repository, Revwalk,
 };
+use regex::Regex;
+use tui::text::Text;
+
+use crate::search::LineHistory;
 
 pub struct CommitInfo<'repo> {
 	pub commit_id: Oid,
 	pub author_name: String,
 	pub author_email: String,
 	pub time: String,
+	pub author_time: git2::Time,
 	pub summary: String,
 	pub message: String,
+	pub parent_ids: Vec<Oid>,
+	pub diff_mode: DiffMode,
 	pub patch: Diff<'repo>,
-	pub stats: Vec<Line<'repo>>,
+	pub patch_options: PatchOptions, // what `patch` and `stats` were computed with
+	pub stats: Vec<String>,
 	pub num_files: usize,
 }
 
+// what a commit is diffed against. only merge commits have a choice
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffMode {
+	Parent(usize),
+	Combined, // only the files that differ from every parent, like `git show --cc`
+	Empty,
+}
+
+impl DiffMode {
+	// parent 0, ..., parent n-1, combined, empty, parent 0
+	pub fn next(self, num_parents: usize) -> DiffMode {
+		match self {
+			DiffMode::Parent(n) if n + 1 < num_parents => DiffMode::Parent(n + 1),
+			DiffMode::Parent(_) => DiffMode::Combined,
+			DiffMode::Combined => DiffMode::Empty,
+			DiffMode::Empty => DiffMode::Parent(0),
+		}
+	}
+
+	pub fn description(self, parent_ids: &[Oid]) -> String {
+		match self {
+			DiffMode::Parent(n) => {
+				let mut parent_id = parent_ids[n].to_string();
+				parent_id.truncate(8);
+				format!("diff against parent {}/{} ({})", n + 1, parent_ids.len(), parent_id)
+			},
+			DiffMode::Combined => "combined diff, counted against parent 1".to_owned(),
+			DiffMode::Empty => "no diff".to_owned(),
+		}
+	}
+}
+
+// the diff options that can be changed in the commit view. the defaults leave rename and copy detection to git
+// config, like `git show` does
+#[derive(Clone, PartialEq)]
+pub struct PatchOptions {
+	pub ignore_all_space: bool,    // -w
+	pub ignore_space_change: bool, // -b
+	pub ignore_space_at_eol: bool, // --ignore-space-at-eol
+	pub context_lines: u32,        // -U
+	pub renames: Option<u16>,      // -M threshold percent, or None for --no-renames
+	pub copies: Option<u16>,       // -C threshold percent
+	pub copies_harder: bool,       // --find-copies-harder
+}
+
+impl Default for PatchOptions {
+	fn default() -> PatchOptions {
+		PatchOptions {
+			ignore_all_space: false,
+			ignore_space_change: false,
+			ignore_space_at_eol: false,
+			context_lines: 3,
+			renames: Some(50),
+			copies: None,
+			copies_harder: false,
+		}
+	}
+}
+
+impl PatchOptions {
+	// whether rename and copy detection differ from the defaults, which follow git config
+	fn find_changed(&self) -> bool {
+		let default = PatchOptions::default();
+		self.renames != default.renames || self.copies != default.copies || self.copies_harder
+	}
+
+	// the same options for `git show` and `git diff`
+	pub fn git_args(&self) -> Vec<String> {
+		let mut args = vec![];
+		if self.ignore_all_space {
+			args.push("--ignore-all-space".to_owned());
+		}
+		if self.ignore_space_change {
+			args.push("--ignore-space-change".to_owned());
+		}
+		if self.ignore_space_at_eol {
+			args.push("--ignore-space-at-eol".to_owned());
+		}
+		if self.context_lines != PatchOptions::default().context_lines {
+			args.push(format!("--unified={}", self.context_lines));
+		}
+		if self.find_changed() {
+			match self.renames {
+				Some(threshold) => args.push(format!("--find-renames={}%", threshold)),
+				None => args.push("--no-renames".to_owned()),
+			}
+			if let Some(threshold) = self.copies {
+				args.push(format!("--find-copies={}%", threshold));
+			}
+			if self.copies_harder {
+				args.push("--find-copies-harder".to_owned());
+			}
+		}
+		args
+	}
+
+	pub fn description(&self) -> String {
+		let args = self.git_args();
+		if args.is_empty() {
+			"default diff options".to_owned()
+		} else {
+			args.join(" ")
+		}
+	}
+
+	fn diff_options(&self) -> DiffOptions {
+		let mut opts = DiffOptions::new();
+		opts.ignore_whitespace(self.ignore_all_space)
+			.ignore_whitespace_change(self.ignore_space_change)
+			.ignore_whitespace_eol(self.ignore_space_at_eol)
+			.context_lines(self.context_lines)
+			.include_unmodified(self.copies_harder); // the unmodified files are where copies can come from
+		opts
+	}
+
+	fn find_options(&self) -> Option<DiffFindOptions> {
+		if !self.find_changed() {
+			return None;
+		}
+		let mut opts = DiffFindOptions::new();
+		opts.renames(self.renames.is_some())
+			.rename_threshold(self.renames.unwrap_or_default())
+			.copies(self.copies.is_some() || self.copies_harder)
+			.copy_threshold(self.copies.unwrap_or(50))
+			.copies_from_unmodified(self.copies_harder)
+			.remove_unmodified(true);
+		Some(opts)
+	}
+}
+
 pub fn log<'repo>(repo: &'repo Repository, revision_range: &str) -> Result<Revwalk<'repo>, git2::Error> {
 	let mut revwalk = repo.revwalk()?;
 	if revision_range.contains("..") {
@@ -34,14 +172,314 @@ pub fn log<'repo>(repo: &'repo Repository, revision_range: &str) -> Result<Revwa
 	return Ok(revwalk);
 }
 
-pub fn next_commit<'repo>(
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterField {
+	Author,
+	Committer,
+	Since,
+	Until,
+	Grep,
+}
+
+impl FilterField {
+	pub fn name(self) -> &'static str {
+		match self {
+			FilterField::Author => "author",
+			FilterField::Committer => "committer",
+			FilterField::Since => "since",
+			FilterField::Until => "until",
+			FilterField::Grep => "grep",
+		}
+	}
+}
+
+// narrows the log like `git log --author --committer --since --until --grep`
+#[derive(Clone, Default)]
+pub struct LogFilter {
+	conditions: Vec<(FilterField, String, Condition)>,
+}
+
+#[derive(Clone)]
+enum Condition {
+	Regex(Regex),
+	Time(i64),
+}
+
+impl LogFilter {
+	// an empty `text` removes the condition
+	pub fn set(&mut self, field: FilterField, text: &str) -> Result<(), String> {
+		let text = text.trim();
+		let condition = match field {
+			_ if text.is_empty() => None,
+			FilterField::Author | FilterField::Committer | FilterField::Grep => {
+				Some(Condition::Regex(Regex::new(text).map_err(|err| err.to_string())?))
+			},
+			FilterField::Since => Some(Condition::Time(parse_date(text, false)?)),
+			FilterField::Until => Some(Condition::Time(parse_date(text, true)?)),
+		};
+		self.conditions.retain(|(f, _, _)| *f != field);
+		if let Some(condition) = condition {
+			self.conditions.push((field, text.to_owned(), condition));
+		}
+		Ok(())
+	}
+
+	pub fn get(&self, field: FilterField) -> &str {
+		match self.conditions.iter().find(|(f, _, _)| *f == field) {
+			Some((_, text, _)) => text,
+			None => "",
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.conditions.clear();
+	}
+
+	// e.g. `author:alice since:2024-01-01`
+	pub fn description(&self) -> String {
+		let conditions: Vec<String> =
+			self.conditions.iter().map(|(field, text, _)| format!("{}:{}", field.name(), text)).collect();
+		conditions.join(" ")
+	}
+
+	pub fn matches(&self, commit: &Commit) -> bool {
+		let signature = |sig: git2::Signature| {
+			format!(
+				"{} <{}>",
+				sig.name().unwrap_or_default(),
+				sig.email().unwrap_or_default()
+			)
+		};
+		self.conditions.iter().all(|(field, _, condition)| match (field, condition) {
+			(FilterField::Author, Condition::Regex(re)) => re.is_match(&signature(commit.author())),
+			(FilterField::Committer, Condition::Regex(re)) => re.is_match(&signature(commit.committer())),
+			(FilterField::Grep, Condition::Regex(re)) => re.is_match(commit.message().unwrap_or_default()),
+			// like git, dates are the commit time rather than the author time
+			(FilterField::Since, Condition::Time(time)) => commit.time().seconds() >= *time,
+			(FilterField::Until, Condition::Time(time)) => commit.time().seconds() <= *time,
+			_ => true,
+		})
+	}
+}
+
+// finds commits whose patch adds or removes something, like `git log -S` and `git log -G`
+#[derive(Clone)]
+pub enum Pickaxe {
+	Count(String), // -S: the number of occurrences in a file changes
+	Line(Regex),   // -G: an added or removed line matches
+}
+
+impl Pickaxe {
+	pub fn description(&self) -> String {
+		match self {
+			Pickaxe::Count(s) => format!("-S {}", s),
+			Pickaxe::Line(re) => format!("-G {}", re.as_str()),
+		}
+	}
+
+	// checks an already computed patch
+	pub fn matches(&self, diff: &Diff) -> Result<bool, git2::Error> {
+		for index in 0..diff.deltas().len() {
+			let Some(patch) = Patch::from_diff(diff, index)? else {
+				continue; // binary
+			};
+			let (mut added, mut removed) = (0, 0);
+			for hunk in 0..patch.num_hunks() {
+				for line in 0..patch.num_lines_in_hunk(hunk)? {
+					let line = patch.line_in_hunk(hunk, line)?;
+					let content = String::from_utf8_lossy(line.content());
+					match (self, line.origin()) {
+						(Pickaxe::Count(s), '+') => added += content.matches(s.as_str()).count(),
+						(Pickaxe::Count(s), '-') => removed += content.matches(s.as_str()).count(),
+						(Pickaxe::Line(re), '+' | '-') if re.is_match(&content) => return Ok(true),
+						_ => {},
+					}
+				}
+			}
+			// the unchanged lines have the same number in the old and new file
+			if added != removed {
+				return Ok(true);
+			}
+		}
+		Ok(false)
+	}
+
+	pub fn matches_commit(&self, repo: &Repository, commit: &Commit) -> Result<bool, git2::Error> {
+		let parent_tree = match commit.parent(0) {
+			Ok(parent) => Some(parent.tree()?),
+			Err(_) => None,
+		};
+		let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+		self.matches(&diff)
+	}
+}
+
+// YYYY-MM-DD in local time or `<n> days|weeks|months|years ago`. a date includes the whole day when `end` is set
+fn parse_date(text: &str, end: bool) -> Result<i64, String> {
+	if let Ok(date) = chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+		let time = if end {
+			date.and_hms_opt(23, 59, 59).unwrap()
+		} else {
+			date.and_hms_opt(0, 0, 0).unwrap()
+		};
+		return match time.and_local_timezone(chrono::Local).earliest() {
+			Some(dt) => Ok(dt.timestamp()),
+			None => Err(format!("invalid local time {}", text)),
+		};
+	}
+	let words: Vec<&str> = text.split_whitespace().collect();
+	if let [n, unit, "ago"] = words[..] {
+		if let Ok(n) = n.parse::<i64>() {
+			let seconds = match unit.trim_end_matches('s') {
+				"minute" => 60,
+				"hour" => 60 * 60,
+				"day" => 24 * 60 * 60,
+				"week" => 7 * 24 * 60 * 60,
+				"month" => 30 * 24 * 60 * 60,
+				"year" => 365 * 24 * 60 * 60,
+				_ => return Err(format!("unknown unit {}", unit)),
+			};
+			return Ok(chrono::Utc::now().timestamp() - n * seconds);
+		}
+	}
+	Err(format!(
+		"couldn't parse date {} (expected YYYY-MM-DD or `2 weeks ago`)",
+		text
+	))
+}
+
+// where a commit comes in the log, found without loading any of the commits before it
+pub fn log_position(
+	repo: &Repository,
+	revision_range: &str,
+	filter: &LogFilter,
+	commit_id: Oid,
+) -> Result<Option<usize>, git2::Error> {
+	let mut index = 0;
+	for id in log(repo, revision_range)? {
+		let id = id?;
+		if !filter.matches(&repo.find_commit(id)?) {
+			continue;
+		}
+		if id == commit_id {
+			return Ok(Some(index));
+		}
+		index += 1;
+	}
+	Ok(None)
+}
+
+// skips commits that don't match the filter without diffing them
+pub fn next_matching_commit<'repo>(
 	repo: &'repo Repository,
 	revwalk: &mut Revwalk,
+	filter: &LogFilter,
+	pickaxe: Option<&Pickaxe>,
 ) -> Result<Option<CommitInfo<'repo>>, git2::Error> {
-	let commit_id = match revwalk.next() {
-		None => return Ok(None),
-		Some(result) => result?,
-	};
+	for commit_id in revwalk {
+		let commit_id = commit_id?;
+		if !filter.matches(&repo.find_commit(commit_id)?) {
+			continue;
+		}
+		let commit_info = commit_info(repo, commit_id)?;
+		match pickaxe {
+			Some(pickaxe) if !pickaxe.matches(&commit_info.patch)? => {},
+			_ => return Ok(Some(commit_info)),
+		}
+	}
+	Ok(None)
+}
+
+// the commits that changed a file, following it back through renames like `git log --follow`
+pub struct FileHistory {
+	pub path: PathBuf,            // as of the newest commit
+	current: PathBuf,             // as of the commits being walked
+	paths: HashMap<Oid, PathBuf>, // what the file was called in each commit found
+}
+
+impl FileHistory {
+	pub fn new(path: PathBuf) -> FileHistory {
+		FileHistory {
+			current: path.clone(),
+			path,
+			paths: HashMap::new(),
+		}
+	}
+
+	// for walking the log again from the start
+	pub fn reset(&mut self) {
+		self.current = self.path.clone();
+		self.paths.clear();
+	}
+
+	pub fn path_at(&self, commit_id: Oid) -> Option<&Path> {
+		self.paths.get(&commit_id).map(PathBuf::as_path)
+	}
+}
+
+pub fn next_file_commit<'repo>(
+	repo: &'repo Repository,
+	revwalk: &mut Revwalk,
+	filter: &LogFilter,
+	history: &mut FileHistory,
+) -> Result<Option<CommitInfo<'repo>>, git2::Error> {
+	for commit_id in revwalk {
+		let commit = repo.find_commit(commit_id?)?;
+		// comparing the blob ids is much cheaper than diffing every commit
+		let entry_id = |tree: &git2::Tree| tree.get_path(&history.current).ok().map(|entry| entry.id());
+		let parent_entry_id = match commit.parent(0) {
+			Ok(parent) => entry_id(&parent.tree()?),
+			Err(_) => None,
+		};
+		if entry_id(&commit.tree()?) == parent_entry_id {
+			continue;
+		}
+
+		// renames have to be followed even through commits that are filtered out
+		let commit_info = commit_info(repo, commit.id())?;
+		let path = history.current.clone();
+		for delta in commit_info.patch.deltas() {
+			if delta.new_file().path() != Some(&path) {
+				continue;
+			}
+			if let git2::Delta::Renamed | git2::Delta::Copied = delta.status() {
+				if let Some(old_path) = delta.old_file().path() {
+					history.current = old_path.to_owned();
+				}
+			}
+		}
+		if filter.matches(&commit) {
+			history.paths.insert(commit.id(), path);
+			return Ok(Some(commit_info));
+		}
+	}
+	Ok(None)
+}
+
+// starts `git log -L` in the background, which follows the lines as they move and the file through renames
+pub fn line_history(repo: &Repository, revision: &str, spec: &str, color: bool) -> Result<LineHistory, git2::Error> {
+	let color_arg = if color { "--color=always" } else { "--color=never" };
+	let mut git = git_command(repo);
+	git.args(["log", color_arg, "--format=%x00%H", "-L", spec, revision]);
+	LineHistory::start(git, spec.to_owned()).map_err(|err| git2::Error::from_str(&format!("git log -L: {}", err)))
+}
+
+// or None if `git log -L` hasn't got to the next one yet
+pub fn next_line_commit<'repo>(
+	repo: &'repo Repository,
+	filter: &LogFilter,
+	history: &mut LineHistory,
+) -> Result<Option<CommitInfo<'repo>>, git2::Error> {
+	while let Some(commit_id) = history.next_found() {
+		if filter.matches(&repo.find_commit(commit_id)?) {
+			return commit_info(repo, commit_id).map(Some);
+		}
+	}
+	Ok(None)
+}
+
+// loads any commit, whether or not it's part of the log
+pub fn commit_info<'repo>(repo: &'repo Repository, commit_id: Oid) -> Result<CommitInfo<'repo>, git2::Error> {
 	let commit = repo.find_commit(commit_id)?;
 	let author = commit.author();
 	let time = match chrono::DateTime::from_timestamp(author.when().seconds(), 0) {
@@ -49,50 +487,325 @@ pub fn next_commit<'repo>(
 		None => "".to_string(),
 	};
 
-	let tree: git2::Tree;
-	let parent_tree = match commit.parent(0) {
-		Ok(parent) => {
-			tree = parent.tree()?;
-			Some(&tree)
-		},
-		Err(_) => None,
-	};
-	let mut patch = repo.diff_tree_to_tree(parent_tree, Some(&commit.tree()?), None)?;
-	patch.find_similar(None)?;
-	let stats = patch.stats()?;
-	let stat_buf = stats.to_buf(DiffStatsFormat::FULL | DiffStatsFormat::INCLUDE_SUMMARY, 100)?;
-	let stat_lines: Vec<Line<'repo>> = stat_buf.as_str().unwrap_or_default().lines().map(format_stat_line).collect();
+	let patch_options = PatchOptions::default();
+	let patch = commit_diff(repo, &commit, DiffMode::Parent(0), &patch_options)?;
+	let (stat_lines, num_files) = diff_stats(&patch)?;
 
-	return Ok(Some(CommitInfo {
+	return Ok(CommitInfo {
 		commit_id,
 		author_name: author.name().unwrap_or_default().to_owned(),
 		author_email: author.email().unwrap_or_default().to_owned(),
 		time,
+		author_time: author.when(),
 		summary: commit.summary().unwrap_or_default().to_owned(),
 		message: commit.message().unwrap_or_default().to_owned(),
+		parent_ids: commit.parent_ids().collect(),
+		diff_mode: DiffMode::Parent(0),
 		patch,
+		patch_options,
 		stats: stat_lines,
-		num_files: stats.files_changed(),
-	}));
+		num_files,
+	});
 }
 
-fn format_stat_line(line: &str) -> Line<'static> {
-	if let Some((path, changes)) = line.split_once(" | ") {
-		if let Some((num_changes, sigils)) = changes.rsplit_once(' ') {
-			let (insertions, deletions) = sigils.split_once('-').unwrap_or((sigils, ""));
-			return Line::from(vec![
-				Span::from(format!("{} | {} ", path, num_changes)),
-				Span::from(insertions.to_owned()).green(),
-				Span::from(deletions.to_owned()).red(),
-			]);
+// recomputes the patch and stats
+pub fn set_diff<'repo>(
+	repo: &'repo Repository,
+	commit_info: &mut CommitInfo<'repo>,
+	mode: DiffMode,
+	options: &PatchOptions,
+) -> Result<(), git2::Error> {
+	let commit = repo.find_commit(commit_info.commit_id)?;
+	let patch = commit_diff(repo, &commit, mode, options)?;
+	(commit_info.stats, commit_info.num_files) = diff_stats(&patch)?;
+	commit_info.patch = patch;
+	commit_info.diff_mode = mode;
+	commit_info.patch_options = options.clone();
+	Ok(())
+}
+
+fn commit_diff<'repo>(
+	repo: &'repo Repository,
+	commit: &Commit,
+	mode: DiffMode,
+	options: &PatchOptions,
+) -> Result<Diff<'repo>, git2::Error> {
+	let tree = commit.tree()?;
+	let parent_tree = |n: usize| -> Result<Option<git2::Tree>, git2::Error> {
+		match commit.parent(n) {
+			Ok(parent) => Ok(Some(parent.tree()?)),
+			Err(_) => Ok(None),
 		}
+	};
+	let mut patch = match mode {
+		DiffMode::Parent(n) => {
+			repo.diff_tree_to_tree(parent_tree(n)?.as_ref(), Some(&tree), Some(&mut options.diff_options()))?
+		},
+		DiffMode::Combined => {
+			// like `git show --cc`, a file that ended up the same as in any parent isn't part of the merge's changes.
+			// the rest are diffed against the first parent, so that's what their counts are for
+			let parent_trees = (0..commit.parent_count()).map(parent_tree).collect::<Result<Vec<_>, _>>()?;
+			let entry_id =
+				|tree: Option<&git2::Tree>, path: &Path| tree.and_then(|t| t.get_path(path).ok()).map(|e| e.id());
+			let first_parent = repo.diff_tree_to_tree(parent_trees[0].as_ref(), Some(&tree), None)?;
+			let mut paths = vec![];
+			for delta in first_parent.deltas() {
+				let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+					continue;
+				};
+				let id = entry_id(Some(&tree), path);
+				if parent_trees.iter().all(|parent| entry_id(parent.as_ref(), path) != id) {
+					paths.push(path.to_string_lossy().into_owned());
+				}
+			}
+			match paths {
+				paths if !paths.is_empty() => {
+					let mut opts = options.diff_options();
+					opts.disable_pathspec_match(true);
+					for path in paths {
+						opts.pathspec(path);
+					}
+					repo.diff_tree_to_tree(parent_tree(0)?.as_ref(), Some(&tree), Some(&mut opts))?
+				},
+				_ => repo.diff_tree_to_tree(Some(&tree), Some(&tree), None)?,
+			}
+		},
+		DiffMode::Empty => repo.diff_tree_to_tree(Some(&tree), Some(&tree), None)?,
+	};
+	patch.find_similar(options.find_options().as_mut())?;
+	Ok(patch)
+}
+
+fn diff_stats(patch: &Diff) -> Result<(Vec<String>, usize), git2::Error> {
+	let stats = patch.stats()?;
+	let stat_buf = stats.to_buf(DiffStatsFormat::FULL | DiffStatsFormat::INCLUDE_SUMMARY, 100)?;
+	let stat_lines = stat_buf.as_str().unwrap_or_default().lines().map(str::to_owned).collect();
+	Ok((stat_lines, stats.files_changed()))
+}
+
+pub struct FileStat {
+	pub path: String,
+	pub old_path: Option<String>, // for renames and copies
+	pub status: char,             // A, M, D, R, C, ... like `git diff --name-status`
+	pub insertions: usize,
+	pub deletions: usize,
+}
+
+pub fn file_stats(diff: &Diff) -> Result<Vec<FileStat>, git2::Error> {
+	let mut stats = vec![];
+	for (index, delta) in diff.deltas().enumerate() {
+		let (insertions, deletions) = match Patch::from_diff(diff, index)? {
+			Some(patch) => {
+				let (_, insertions, deletions) = patch.line_stats()?;
+				(insertions, deletions)
+			},
+			None => (0, 0),
+		};
+		let path_of = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().into_owned());
+		let old_path = match delta.status() {
+			git2::Delta::Renamed | git2::Delta::Copied => path_of(delta.old_file()),
+			_ => None,
+		};
+		stats.push(FileStat {
+			path: path_of(delta.new_file()).unwrap_or_default(),
+			old_path,
+			status: delta_status_char(delta.status()),
+			insertions,
+			deletions,
+		});
+	}
+	Ok(stats)
+}
+
+fn delta_status_char(status: git2::Delta) -> char {
+	match status {
+		git2::Delta::Added => 'A',
+		git2::Delta::Deleted => 'D',
+		git2::Delta::Modified => 'M',
+		git2::Delta::Renamed => 'R',
+		git2::Delta::Copied => 'C',
+		git2::Delta::Typechange => 'T',
+		git2::Delta::Untracked => '?',
+		git2::Delta::Conflicted => 'U',
+		_ => ' ',
 	}
-	Line::from(line.to_owned())
+}
+
+// the lines of the new file (or the old file, for `parent`) covered by its first hunk, as `start,end` for `git log -L`
+pub fn first_hunk_range(diff: &Diff, file_index: usize, parent: bool) -> Result<Option<String>, git2::Error> {
+	let Some(patch) = Patch::from_diff(diff, file_index)? else {
+		return Ok(None);
+	};
+	if patch.num_hunks() == 0 {
+		return Ok(None);
+	}
+	let (hunk, _) = patch.hunk(0)?;
+	let (start, lines) = if parent {
+		(hunk.old_start(), hunk.old_lines())
+	} else {
+		(hunk.new_start(), hunk.new_lines())
+	};
+	let start = start.max(1);
+	Ok(Some(format!("{},{}", start, start + lines.saturating_sub(1))))
+}
+
+// where a line of the old file ends up in the new one: moved by the hunks before it, or for a line that was removed,
+// the line that comes after it
+pub fn new_line_number(diff: &Diff, file_index: usize, line: u32) -> Result<u32, git2::Error> {
+	let Some(patch) = Patch::from_diff(diff, file_index)? else {
+		return Ok(line);
+	};
+	let mut shift = 0;
+	for hunk_index in 0..patch.num_hunks() {
+		let (hunk, _) = patch.hunk(hunk_index)?;
+		if line < hunk.old_start() {
+			break;
+		}
+		// a hunk that only adds lines adds them after old_start, and one that only removes lines removes them after
+		// new_start
+		let old_end = hunk.old_start() + hunk.old_lines().max(1);
+		let new_end = hunk.new_start() + hunk.new_lines().max(1);
+		if line >= old_end {
+			shift = i64::from(new_end) - i64::from(old_end);
+			continue;
+		}
+		let mut found = false;
+		let mut last_new = hunk.new_start();
+		for line_index in 0..patch.num_lines_in_hunk(hunk_index)? {
+			let diff_line = patch.line_in_hunk(hunk_index, line_index)?;
+			found |= diff_line.old_lineno() == Some(line);
+			if let Some(new_line) = diff_line.new_lineno() {
+				if found {
+					return Ok(new_line);
+				}
+				last_new = new_line;
+			}
+		}
+		if found {
+			return Ok(last_new.max(1));
+		}
+	}
+	Ok(u32::try_from(i64::from(line) + shift).unwrap_or(1).max(1))
+}
+
+// the `@@ -a,b +c,d @@` part of each hunk header of a file, for finding the hunks in `git show` output
+pub fn hunk_headers(diff: &Diff, file_index: usize) -> Result<Vec<String>, git2::Error> {
+	let Some(patch) = Patch::from_diff(diff, file_index)? else {
+		return Ok(vec![]);
+	};
+	let mut headers = Vec::with_capacity(patch.num_hunks());
+	for hunk_index in 0..patch.num_hunks() {
+		let (hunk, _) = patch.hunk(hunk_index)?;
+		let header = String::from_utf8_lossy(hunk.header());
+		let end = header.get(2..).and_then(|rest| rest.find("@@")).map_or(header.len(), |i| i + 4);
+		headers.push(header[..end].to_owned());
+	}
+	Ok(headers)
+}
+
+pub enum FileAt {
+	Text { contents: String, changed: HashSet<u32> }, // changed has the 1-based line numbers that the diff added or removed
+	Binary,
+	Missing, // added or deleted by the diff
+}
+
+// one file of the diff as of the commit, or as of the parent it's diffed against. read from the tree, not the worktree
+pub fn file_at(repo: &Repository, diff: &Diff, file_index: usize, parent: bool) -> Result<FileAt, git2::Error> {
+	let Some(delta) = diff.get_delta(file_index) else {
+		return Ok(FileAt::Missing);
+	};
+	let file = if parent { delta.old_file() } else { delta.new_file() };
+	if file.id().is_zero() {
+		return Ok(FileAt::Missing);
+	}
+	let blob = repo.find_blob(file.id())?;
+	if blob.is_binary() {
+		return Ok(FileAt::Binary);
+	}
+	let mut changed = HashSet::new();
+	if let Some(patch) = Patch::from_diff(diff, file_index)? {
+		let origin = if parent { '-' } else { '+' };
+		for hunk_index in 0..patch.num_hunks() {
+			for line_index in 0..patch.num_lines_in_hunk(hunk_index)? {
+				let line = patch.line_in_hunk(hunk_index, line_index)?;
+				if line.origin() == origin {
+					changed.extend(if parent { line.old_lineno() } else { line.new_lineno() });
+				}
+			}
+		}
+	}
+	Ok(FileAt::Text {
+		contents: String::from_utf8_lossy(blob.content()).into_owned(),
+		changed,
+	})
+}
+
+// the plain patch for one file of the diff, or for the whole diff
+pub fn patch_text(diff: &Diff, file_index: Option<usize>) -> Result<String, git2::Error> {
+	let mut buf = vec![];
+	match file_index {
+		Some(index) => {
+			if let Some(mut patch) = Patch::from_diff(diff, index)? {
+				buf.extend_from_slice(&patch.to_buf()?);
+			}
+		},
+		None => diff.print(DiffFormat::Patch, |_, _, line| {
+			if let '+' | '-' | ' ' = line.origin() {
+				buf.push(line.origin() as u8);
+			}
+			buf.extend_from_slice(line.content());
+			true
+		})?,
+	}
+	Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+// `git format-patch` style emails (mbox with From/Subject/Date headers and a diffstat) for a series of commits,
+// oldest first. returns the file name for each patch along with its contents
+pub fn format_patches(repo: &Repository, commit_ids: &[Oid]) -> Result<Vec<(String, Vec<u8>)>, git2::Error> {
+	let mut patches = vec![];
+	for (i, commit_id) in commit_ids.iter().enumerate() {
+		let commit = repo.find_commit(*commit_id)?;
+		let diff = commit_diff(repo, &commit, DiffMode::Parent(0), &PatchOptions::default())?;
+		let summary = commit.summary().unwrap_or_default();
+		let email = Email::from_diff(
+			&diff,
+			i + 1,
+			commit_ids.len(),
+			commit_id,
+			summary,
+			commit.body().unwrap_or_default(),
+			&commit.author(),
+			&mut EmailCreateOptions::new(),
+		)?;
+		patches.push((
+			format!("{:04}-{}.patch", i + 1, patch_file_name(summary)),
+			email.as_slice().to_vec(),
+		));
+	}
+	Ok(patches)
+}
+
+// like `git format-patch`: keep alphanumerics, `.` and `_`, collapse everything else into `-`
+fn patch_file_name(summary: &str) -> String {
+	let mut name = String::new();
+	for c in summary.chars() {
+		if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+			name.push(c);
+		} else if !name.is_empty() && !name.ends_with('-') {
+			name.push('-');
+		}
+		if name.len() >= 52 {
+			break;
+		}
+	}
+	name.trim_end_matches(['-', '.']).to_owned()
 }
 
 pub struct Decorations {
 	pub branches: HashMap<Oid, Vec<(String, BranchType)>>,
 	pub tags: HashMap<Oid, Vec<String>>,
+	pub worktrees: HashMap<String, String>, // local branch name to the worktree that has it checked out
 }
 
 pub fn decorations(repo: &Repository) -> Result<Decorations, git2::Error> {
@@ -115,7 +828,64 @@ pub fn decorations(repo: &Repository) -> Result<Decorations, git2::Error> {
 		true
 	})?;
 
-	Ok(Decorations { branches, tags })
+	Ok(Decorations {
+		branches,
+		tags,
+		worktrees: worktree_branches(repo),
+	})
+}
+
+// only filled in when there are linked worktrees, since otherwise it's just the current branch.
+// a worktree that can't be read is left out, it's not worth failing the whole log over
+fn worktree_branches(repo: &Repository) -> HashMap<String, String> {
+	let mut worktrees = HashMap::new();
+	// from a linked worktree, the other worktrees are listed by the main one
+	let opened;
+	let main = if repo.is_worktree() {
+		// .git/worktrees/<name>/commondir points back at .git
+		let main = fs::read_to_string(repo.path().join("commondir"))
+			.ok()
+			.and_then(|commondir| Repository::open(repo.path().join(commondir.trim())).ok());
+		match main {
+			Some(main) => {
+				opened = main;
+				&opened
+			},
+			None => return worktrees,
+		}
+	} else {
+		repo
+	};
+	let names = match main.worktrees() {
+		Ok(names) if !names.is_empty() => names,
+		_ => return worktrees,
+	};
+
+	let head_branch = |repo: &Repository| -> Option<String> {
+		let head = repo.find_reference("HEAD").ok()?;
+		head.symbolic_target().and_then(|target| target.strip_prefix("refs/heads/")).map(str::to_owned)
+	};
+	if let Some(workdir) = main.workdir() {
+		if let Some(branch) = head_branch(main) {
+			let name = workdir.file_name().unwrap_or(workdir.as_os_str()).to_string_lossy().into_owned();
+			worktrees.insert(branch, name);
+		}
+	}
+	for name in names.iter().flatten() {
+		let Ok(worktree) = main.find_worktree(name) else {
+			continue;
+		};
+		if worktree.validate().is_err() {
+			continue; // its directory is gone but it hasn't been pruned yet
+		}
+		let Ok(worktree_repo) = Repository::open_from_worktree(&worktree) else {
+			continue;
+		};
+		if let Some(branch) = head_branch(&worktree_repo) {
+			worktrees.insert(branch, name.to_owned());
+		}
+	}
+	worktrees
 }
 
 fn push<T>(map: &mut HashMap<Oid, Vec<T>>, commit_id: Oid, name: T) {
@@ -127,29 +897,54 @@ fn push<T>(map: &mut HashMap<Oid, Vec<T>>, commit_id: Oid, name: T) {
 	};
 }
 
-pub fn show(repo: &Repository, commit_id: Oid, file_path: &Path) -> Text<'static> {
-	let repo_path = repo.workdir().unwrap();
-	let git_show = match Command::new("git")
-		.args([
-			OsString::from("show").as_os_str(),
-			OsString::from("--format=").as_os_str(),
-			OsString::from("--color=always").as_os_str(),
-			OsString::from("--expand-tabs=4").as_os_str(),
-			OsString::from(commit_id.to_string()).as_os_str(),
-			OsString::from("--").as_os_str(), // needed for files that don't exist in the worktree
-			file_path.as_os_str(),
-		])
-		.current_dir(repo_path)
-		.stdout(Stdio::piped())
-		.spawn()
-	{
+// runs git on this repo whether it's bare, a linked worktree or was found through $GIT_DIR
+pub fn git_command(repo: &Repository) -> Command {
+	let mut git = Command::new("git");
+	git.arg("--git-dir").arg(repo.path());
+	// paths are relative to the top of the worktree
+	git.current_dir(repo.workdir().unwrap_or(repo.path()));
+	git
+}
+
+// the whole commit when `file_path` is None
+pub fn show(repo: &Repository, commit_info: &CommitInfo, file_path: Option<&Path>, color: bool) -> Text<'static> {
+	let color_arg = OsString::from(if color { "--color=always" } else { "--color=never" });
+	let mut args = match commit_info.diff_mode {
+		// `git show` would show the combined diff for merges
+		DiffMode::Parent(n) if commit_info.parent_ids.len() > 1 => vec![
+			OsString::from("diff"),
+			color_arg,
+			OsString::from(commit_info.parent_ids[n].to_string()),
+			OsString::from(commit_info.commit_id.to_string()),
+		],
+		_ => vec![
+			OsString::from("show"),
+			OsString::from("--format="),
+			color_arg,
+			OsString::from("--expand-tabs=4"),
+			OsString::from(commit_info.commit_id.to_string()),
+		],
+	};
+	args.extend(commit_info.patch_options.git_args().into_iter().map(OsString::from));
+	if let Some(file_path) = file_path {
+		args.push(OsString::from("--")); // needed for files that don't exist in the worktree
+		args.push(file_path.as_os_str().to_owned());
+	}
+	let git_show = match git_command(repo).args(&args).stdout(Stdio::piped()).spawn() {
 		Ok(proc) => proc,
 		Err(e) => return Text::raw(format!("git show: {}", e)),
 	};
-	let mut delta = Command::new("delta");
-	delta.stdin(Stdio::from(git_show.stdout.unwrap()));
+	let output = if color {
+		let mut delta = Command::new("delta");
+		// by default, delta rewrites hunk headers as `file:line:`, and the hunks are found by their headers
+		delta.arg("--hunk-header-style=raw");
+		delta.stdin(Stdio::from(git_show.stdout.unwrap()));
+		delta.output()
+	} else {
+		git_show.wait_with_output()
+	};
 
-	let buf = match delta.output() {
+	let buf = match output {
 		Ok(o) => {
 			if o.status.success() {
 				o.stdout
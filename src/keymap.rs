@@ -0,0 +1,577 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use git2::Config;
+use tui::text::{Line, Text};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+	Help,
+	Quit,
+	Suspend,
+	// log
+	ShortLog,
+	RegularLog,
+	StatLog,
+	NextCommit,
+	PrevCommit,
+	FirstCommit,
+	ShowCommit,
+	ExecGitLog,
+	Pickaxe,
+	PickaxeRegex,
+	// commit
+	NextFile,
+	PrevFile,
+	ToggleDir,
+	ToggleAllDirs,
+	SortFiles,
+	FilterFiles,
+	LineDown,
+	LineUp,
+	MessageDown,
+	MessageUp,
+	CycleDiffMode,
+	Parent,
+	Child,
+	Back,
+	Forward,
+	FileHistory,
+	LineHistory,
+	SelectLines,
+	Search,
+	NextMatch,
+	PrevMatch,
+	NextHunk,
+	PrevHunk,
+	WholeCommit,
+	CycleFileView,
+	Edit,
+	MoreContext,
+	LessContext,
+	DiffOptions,
+	// after DiffOptions
+	IgnoreAllSpace,
+	IgnoreSpaceChange,
+	IgnoreSpaceAtEol,
+	RenameThreshold,
+	CopyThreshold,
+	CopiesHarder,
+	ResetDiffOptions,
+	// both
+	HalfPageDown,
+	HalfPageUp,
+	Yank,
+	// after Yank
+	YankId,
+	YankShortId,
+	YankSummary,
+	YankMessage,
+	YankPatch,
+	YankEmail,
+	Mark,
+	FormatPatch,
+	Jump,
+	Filter,
+	// after Filter
+	FilterAuthor,
+	FilterCommitter,
+	FilterSince,
+	FilterUntil,
+	FilterGrep,
+	ClearFilter,
+	// --pick
+	Pick,
+}
+
+impl Action {
+	// the name used in git config, e.g. `gil.log.next-commit`
+	fn name(self) -> &'static str {
+		match self {
+			Action::Help => "help",
+			Action::Quit => "quit",
+			Action::Suspend => "suspend",
+			Action::ShortLog => "short-log",
+			Action::RegularLog => "regular-log",
+			Action::StatLog => "stat-log",
+			Action::NextCommit => "next-commit",
+			Action::PrevCommit => "prev-commit",
+			Action::FirstCommit => "first-commit",
+			Action::ShowCommit => "show-commit",
+			Action::ExecGitLog => "exec-git-log",
+			Action::Pickaxe => "pickaxe",
+			Action::PickaxeRegex => "pickaxe-regex",
+			Action::NextFile => "next-file",
+			Action::PrevFile => "prev-file",
+			Action::ToggleDir => "toggle-dir",
+			Action::ToggleAllDirs => "toggle-all-dirs",
+			Action::SortFiles => "sort-files",
+			Action::FilterFiles => "filter-files",
+			Action::LineDown => "line-down",
+			Action::LineUp => "line-up",
+			Action::MessageDown => "message-down",
+			Action::MessageUp => "message-up",
+			Action::CycleDiffMode => "cycle-diff-mode",
+			Action::Parent => "parent",
+			Action::Child => "child",
+			Action::Back => "back",
+			Action::Forward => "forward",
+			Action::FileHistory => "file-history",
+			Action::LineHistory => "line-history",
+			Action::SelectLines => "select-lines",
+			Action::Search => "search",
+			Action::NextMatch => "next-match",
+			Action::PrevMatch => "prev-match",
+			Action::NextHunk => "next-hunk",
+			Action::PrevHunk => "prev-hunk",
+			Action::WholeCommit => "whole-commit",
+			Action::CycleFileView => "cycle-file-view",
+			Action::Edit => "edit",
+			Action::MoreContext => "more-context",
+			Action::LessContext => "less-context",
+			Action::DiffOptions => "diff-options",
+			Action::IgnoreAllSpace => "ignore-all-space",
+			Action::IgnoreSpaceChange => "ignore-space-change",
+			Action::IgnoreSpaceAtEol => "ignore-space-at-eol",
+			Action::RenameThreshold => "rename-threshold",
+			Action::CopyThreshold => "copy-threshold",
+			Action::CopiesHarder => "copies-harder",
+			Action::ResetDiffOptions => "reset",
+			Action::HalfPageDown => "half-page-down",
+			Action::HalfPageUp => "half-page-up",
+			Action::Yank => "yank",
+			Action::YankId => "id",
+			Action::YankShortId => "short-id",
+			Action::YankSummary => "summary",
+			Action::YankMessage => "message",
+			Action::YankPatch => "patch",
+			Action::YankEmail => "email",
+			Action::Mark => "mark",
+			Action::FormatPatch => "format-patch",
+			Action::Jump => "jump",
+			Action::Filter => "filter",
+			Action::FilterAuthor => "author",
+			Action::FilterCommitter => "committer",
+			Action::FilterSince => "since",
+			Action::FilterUntil => "until",
+			Action::FilterGrep => "grep",
+			Action::ClearFilter => "clear",
+			Action::Pick => "pick",
+		}
+	}
+
+	fn description(self) -> &'static str {
+		match self {
+			Action::Help => "this help",
+			Action::Quit => "close window or file history",
+			Action::Suspend => "suspend to the shell",
+			Action::ShortLog => "short log",
+			Action::RegularLog => "regular log",
+			Action::StatLog => "log with stat",
+			Action::NextCommit => "next commit",
+			Action::PrevCommit => "previous commit",
+			Action::FirstCommit => "first commit",
+			Action::ShowCommit => "show commit",
+			Action::ExecGitLog => "exec git log",
+			Action::Pickaxe => "find commits that add or remove a string (-S)",
+			Action::PickaxeRegex => "find commits with an added or removed line matching a regex (-G)",
+			Action::NextFile => "next file",
+			Action::PrevFile => "previous file",
+			Action::ToggleDir => "open or close the directory",
+			Action::ToggleAllDirs => "open or close every directory",
+			Action::SortFiles => "sort files by path or by lines changed",
+			Action::FilterFiles => "show only the files matching a fuzzy pattern",
+			Action::LineDown => "down one line",
+			Action::LineUp => "up one line",
+			Action::MessageDown => "scroll commit message down",
+			Action::MessageUp => "scroll commit message up",
+			Action::CycleDiffMode => "merges: diff against each parent, combined or none",
+			Action::Parent => "go to the parent (type a number first for the Nth parent of a merge)",
+			Action::Child => "go to a child (type a number first for the Nth child)",
+			Action::Back => "back to the previously viewed commit",
+			Action::Forward => "forward again",
+			Action::FileHistory => "log of the commits that changed the selected file",
+			Action::LineHistory => "log of the commits that changed some lines of the selected file",
+			Action::SelectLines => "select lines of the whole file for L: the top line, then again after scrolling",
+			Action::Search => "search the patch",
+			Action::NextMatch => "next search match",
+			Action::PrevMatch => "previous search match",
+			Action::NextHunk => "next hunk",
+			Action::PrevHunk => "previous hunk",
+			Action::WholeCommit => "show every file in one patch or one file at a time",
+			Action::CycleFileView => "show the patch, the whole file or the whole file before the commit",
+			Action::Edit => "open the file in $EDITOR at the hunk at the top",
+			Action::MoreContext => "more context lines",
+			Action::LessContext => "fewer context lines",
+			Action::DiffOptions => "change diff options, then:",
+			Action::IgnoreAllSpace => "ignore all whitespace (-w)",
+			Action::IgnoreSpaceChange => "ignore changes in amount of whitespace (-b)",
+			Action::IgnoreSpaceAtEol => "ignore whitespace at end of line",
+			Action::RenameThreshold => "rename detection threshold (-M)",
+			Action::CopyThreshold => "copy detection threshold (-C)",
+			Action::CopiesHarder => "look for copies of unmodified files too",
+			Action::ResetDiffOptions => "back to the defaults",
+			Action::HalfPageDown => "down half a window",
+			Action::HalfPageUp => "up half a window",
+			Action::Yank => "copy to clipboard, then:",
+			Action::YankId => "commit id",
+			Action::YankShortId => "short commit id",
+			Action::YankSummary => "summary",
+			Action::YankMessage => "full message",
+			Action::YankPatch => "patch",
+			Action::YankEmail => "marked or selected commits as format-patch emails",
+			Action::Mark => "mark or unmark commit",
+			Action::FormatPatch => "write marked or selected commits as patch files",
+			Action::Jump => "go to a commit, ref or revspec like HEAD~5",
+			Action::Filter => "filter the log, then:",
+			Action::FilterAuthor => "author regex",
+			Action::FilterCommitter => "committer regex",
+			Action::FilterSince => "since date",
+			Action::FilterUntil => "until date",
+			Action::FilterGrep => "message regex",
+			Action::ClearFilter => "clear all filters",
+			Action::Pick => "print the marked or selected commits and exit",
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyBinding {
+	code: KeyCode,
+	modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+	const fn new(code: KeyCode) -> KeyBinding {
+		KeyBinding {
+			code,
+			modifiers: KeyModifiers::NONE,
+		}
+	}
+
+	fn matches(&self, key: &KeyEvent) -> bool {
+		// shift is implied by the character itself
+		let modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+		return self.code == key.code && self.modifiers == modifiers;
+	}
+
+	fn parse(s: &str) -> Option<KeyBinding> {
+		let mut modifiers = KeyModifiers::NONE;
+		let mut rest = s;
+		loop {
+			if let Some(r) = rest.strip_prefix("ctrl-").or_else(|| rest.strip_prefix("C-")) {
+				modifiers |= KeyModifiers::CONTROL;
+				rest = r;
+			} else if let Some(r) = rest.strip_prefix("alt-").or_else(|| rest.strip_prefix("M-")) {
+				modifiers |= KeyModifiers::ALT;
+				rest = r;
+			} else {
+				break;
+			}
+		}
+		let code = match rest {
+			"up" | "↑" => KeyCode::Up,
+			"down" | "↓" => KeyCode::Down,
+			"left" | "←" => KeyCode::Left,
+			"right" | "→" => KeyCode::Right,
+			"pgup" => KeyCode::PageUp,
+			"pgdown" => KeyCode::PageDown,
+			"home" => KeyCode::Home,
+			"end" => KeyCode::End,
+			"enter" => KeyCode::Enter,
+			"esc" => KeyCode::Esc,
+			"tab" => KeyCode::Tab,
+			"shift-tab" | "backtab" => KeyCode::BackTab,
+			"backspace" => KeyCode::Backspace,
+			"space" => KeyCode::Char(' '),
+			_ => {
+				let mut chars = rest.chars();
+				match (chars.next(), chars.next()) {
+					// terminals send ctrl-W the same as ctrl-w
+					(Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+						KeyCode::Char(c.to_ascii_lowercase())
+					},
+					(Some(c), None) => KeyCode::Char(c),
+					_ => return None,
+				}
+			},
+		};
+		Some(KeyBinding { code, modifiers })
+	}
+
+	fn display(&self) -> String {
+		let mut s = String::new();
+		if self.modifiers.contains(KeyModifiers::CONTROL) {
+			s.push_str("ctrl-");
+		}
+		if self.modifiers.contains(KeyModifiers::ALT) {
+			s.push_str("alt-");
+		}
+		match self.code {
+			KeyCode::Up => s.push('↑'),
+			KeyCode::Down => s.push('↓'),
+			KeyCode::Left => s.push('←'),
+			KeyCode::Right => s.push('→'),
+			KeyCode::PageUp => s.push_str("pgup"),
+			KeyCode::PageDown => s.push_str("pgdown"),
+			KeyCode::Home => s.push_str("home"),
+			KeyCode::End => s.push_str("end"),
+			KeyCode::Enter => s.push_str("enter"),
+			KeyCode::Esc => s.push_str("esc"),
+			KeyCode::Tab => s.push_str("tab"),
+			KeyCode::BackTab => s.push_str("shift-tab"),
+			KeyCode::Backspace => s.push_str("backspace"),
+			KeyCode::Char(' ') => s.push_str("space"),
+			KeyCode::Char(c) => s.push(c),
+			_ => s.push('?'),
+		}
+		s
+	}
+}
+
+// actions are grouped so that the help text can separate them with blank lines
+pub struct Bindings {
+	groups: Vec<Vec<(Action, Vec<KeyBinding>)>>,
+}
+
+impl Bindings {
+	fn new(groups: &[&[(Action, &[KeyCode])]]) -> Bindings {
+		Bindings {
+			groups: groups
+				.iter()
+				.map(|group| {
+					group
+						.iter()
+						.map(|(action, codes)| (*action, codes.iter().map(|code| KeyBinding::new(*code)).collect()))
+						.collect()
+				})
+				.collect(),
+		}
+	}
+
+	pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+		for group in &self.groups {
+			for (action, keys) in group {
+				if keys.iter().any(|k| k.matches(key)) {
+					return Some(*action);
+				}
+			}
+		}
+		None
+	}
+
+	fn rebind(&mut self, name: &str, keys: Vec<KeyBinding>) -> bool {
+		let mut found = false;
+		for group in &mut self.groups {
+			for (action, bound) in group.iter_mut() {
+				if action.name() == name {
+					*bound = keys.clone();
+					found = true;
+				} else {
+					// a key rebound by the user takes precedence over its default action
+					bound.retain(|k| !keys.contains(k));
+				}
+			}
+		}
+		found
+	}
+
+	// `sublists` are the bindings for the key pressed after an action, listed under it
+	fn help_lines(&self, indent: &str, sublists: &[(Action, &Bindings)]) -> Vec<Line<'static>> {
+		let mut lines = vec![];
+		for group in &self.groups {
+			if !lines.is_empty() && group.iter().any(|(_, keys)| !keys.is_empty()) {
+				lines.push(Line::from(""));
+			}
+			for (action, keys) in group {
+				if keys.is_empty() {
+					continue;
+				}
+				let keys = keys.iter().map(KeyBinding::display).collect::<Vec<_>>().join("  ");
+				lines.push(Line::from(format!("{}{:<11} {}", indent, keys, action.description())));
+				if let Some((_, sublist)) = sublists.iter().find(|(a, _)| a == action) {
+					lines.extend(sublist.help_lines(&format!("{}    ", indent), &[]));
+				}
+			}
+		}
+		lines
+	}
+}
+
+pub struct Keymap {
+	pub log: Bindings,
+	pub commit: Bindings,
+	pub yank: Bindings,   // the key pressed after Action::Yank
+	pub filter: Bindings, // the key pressed after Action::Filter
+	pub diff: Bindings,   // the key pressed after Action::DiffOptions
+	pub pick: Bindings,   // checked before log and commit in --pick mode
+}
+
+impl Default for Keymap {
+	fn default() -> Keymap {
+		use Action::*;
+		use KeyCode::{BackTab, Backspace, Char, Down, Enter, Esc, Home, Left, PageDown, PageUp, Right, Tab, Up};
+		let mut keymap = Keymap {
+			log: Bindings::new(&[
+				&[(Help, &[Char('h')]), (Quit, &[Char('q'), Esc])],
+				&[(Suspend, &[])],
+				&[
+					(ShortLog, &[Char('1')]),
+					(RegularLog, &[Char('2')]),
+					(StatLog, &[Char('3')]),
+				],
+				&[
+					(NextCommit, &[Char('j'), Down]),
+					(PrevCommit, &[Char('k'), Up]),
+					(HalfPageDown, &[Char('d'), PageDown]),
+					(HalfPageUp, &[Char('u'), PageUp]),
+					(FirstCommit, &[Char('g'), Home]),
+				],
+				&[(Mark, &[Char(' ')]), (FormatPatch, &[Char('f')])],
+				&[
+					(ShowCommit, &[Enter]),
+					(Jump, &[Char(':')]),
+					(Pickaxe, &[Char('S')]),
+					(PickaxeRegex, &[Char('G')]),
+					(Filter, &[Char('/')]),
+					(ExecGitLog, &[Char('x')]),
+					(Yank, &[Char('y')]),
+				],
+			]),
+			commit: Bindings::new(&[
+				&[(Help, &[Char('h')]), (Quit, &[Char('q'), Esc])],
+				&[(Suspend, &[])],
+				&[
+					(NextFile, &[Tab, Char(']')]),
+					(PrevFile, &[BackTab, Char('[')]),
+					(ToggleDir, &[Char('z')]),
+					(ToggleAllDirs, &[Char('Z')]),
+					(SortFiles, &[Char('s')]),
+					(FilterFiles, &[Char('F')]),
+					(CycleDiffMode, &[Char('m')]),
+					(WholeCommit, &[Char('a')]),
+					(CycleFileView, &[Char('v')]),
+					(MoreContext, &[Char('+'), Char('=')]),
+					(LessContext, &[Char('-')]),
+					(DiffOptions, &[Char('o')]),
+					(FileHistory, &[Char('H')]),
+					(LineHistory, &[Char('L')]),
+					(SelectLines, &[Char('V')]),
+					(Edit, &[Char('e')]),
+				],
+				&[
+					(LineDown, &[Char('j')]),
+					(LineUp, &[Char('k')]),
+					(HalfPageDown, &[Char('d'), PageDown]),
+					(HalfPageUp, &[Char('u'), PageUp]),
+					(NextHunk, &[Char('}')]),
+					(PrevHunk, &[Char('{')]),
+				],
+				&[(MessageDown, &[Down]), (MessageUp, &[Up])],
+				&[
+					(Search, &[Char('/')]),
+					(NextMatch, &[Char('n')]),
+					(PrevMatch, &[Char('N')]),
+				],
+				&[(NextCommit, &[]), (PrevCommit, &[])],
+				&[
+					(Parent, &[Char('P')]),
+					(Child, &[Char('C')]),
+					(Back, &[Left, Backspace]),
+					(Forward, &[Right]),
+					(Jump, &[Char(':')]),
+				],
+				&[(FormatPatch, &[Char('f')]), (Yank, &[Char('y')])],
+			]),
+			yank: Bindings::new(&[&[
+				(YankId, &[Char('h')]),
+				(YankShortId, &[Char('s')]),
+				(YankSummary, &[Char('t')]),
+				(YankMessage, &[Char('m')]),
+				(YankPatch, &[Char('p')]),
+				(YankEmail, &[Char('e')]),
+			]]),
+			filter: Bindings::new(&[&[
+				(FilterAuthor, &[Char('a')]),
+				(FilterCommitter, &[Char('c')]),
+				(FilterSince, &[Char('s')]),
+				(FilterUntil, &[Char('u')]),
+				(FilterGrep, &[Char('g')]),
+				(ClearFilter, &[Char('x')]),
+			]]),
+			diff: Bindings::new(&[&[
+				(IgnoreAllSpace, &[Char('w')]),
+				(IgnoreSpaceChange, &[Char('b')]),
+				(IgnoreSpaceAtEol, &[Char('e')]),
+				(RenameThreshold, &[Char('r')]),
+				(CopyThreshold, &[Char('c')]),
+				(CopiesHarder, &[Char('C')]),
+				(ResetDiffOptions, &[Char('x')]),
+			]]),
+			pick: Bindings::new(&[&[(Pick, &[Enter])]]),
+		};
+		// the only default that needs a modifier
+		let suspend = vec![KeyBinding {
+			code: Char('z'),
+			modifiers: KeyModifiers::CONTROL,
+		}];
+		keymap.log.rebind(Suspend.name(), suspend.clone());
+		keymap.commit.rebind(Suspend.name(), suspend);
+		keymap
+	}
+}
+
+impl Keymap {
+	// help for the log or commit bindings, with what can be copied, filtered or changed under the keys for that
+	pub fn help_text(&self, bindings: &Bindings, pick: bool) -> Text<'static> {
+		let mut lines = vec![];
+		if pick {
+			lines.extend(self.pick.help_lines("", &[]));
+			lines.push(Line::from(""));
+		}
+		lines.extend(bindings.help_lines(
+			"",
+			&[
+				(Action::Yank, &self.yank),
+				(Action::Filter, &self.filter),
+				(Action::DiffOptions, &self.diff),
+			],
+		));
+		lines.into()
+	}
+
+	// reads `gil.<context>.<action>` from git config, where context is log, commit, yank, filter, diff or pick, e.g.
+	//   git config --global gil.commit.next-file 'ctrl-n n'
+	// each value is a space-separated list of keys that replaces the default keys for that action
+	pub fn from_config(config: &Config) -> Result<Keymap, String> {
+		let mut keymap = Keymap::default();
+		let mut entries = config
+			.entries(Some("^gil\\.(log|commit|yank|filter|diff|pick)\\."))
+			.map_err(|e| e.message().to_owned())?;
+		while let Some(entry) = entries.next() {
+			let entry = entry.map_err(|e| e.message().to_owned())?;
+			let name = entry.name().unwrap_or_default();
+			let Some((context, action)) = name.strip_prefix("gil.").and_then(|n| n.split_once('.')) else {
+				continue;
+			};
+			let value = entry.value().unwrap_or_default();
+			let mut keys = vec![];
+			for key in value.split_whitespace() {
+				match KeyBinding::parse(key) {
+					Some(binding) => keys.push(binding),
+					None => return Err(format!("{}: unknown key {:?}", name, key)),
+				}
+			}
+			let bindings = match context {
+				"log" => &mut keymap.log,
+				"commit" => &mut keymap.commit,
+				"yank" => &mut keymap.yank,
+				"filter" => &mut keymap.filter,
+				"diff" => &mut keymap.diff,
+				_ => &mut keymap.pick,
+			};
+			if !bindings.rebind(action, keys) {
+				return Err(format!("{}: unknown action {:?}", name, action));
+			}
+		}
+		Ok(keymap)
+	}
+}
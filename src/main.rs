@@ -1,79 +1,260 @@
 use git2::Repository;
-use std::env;
+use std::{
+	env,
+	io::{self, IsTerminal},
+	panic,
+	path::{Path, PathBuf},
+	process,
+};
+use terminal::LogMode;
 
+mod clipboard;
+mod export;
+mod file_list;
 mod git;
+mod keymap;
+mod print;
+mod search;
+mod settings;
 mod terminal;
+mod theme;
+
+const USAGE: &str = "[rev] [--show | --pick | --format=json|ndjson|csv] [--mode=short|regular|stat] \
+	[--color=auto|always|never] [-L start,end:file | -L :function:file] [-S string | -G regex]";
 
 fn main() {
 	let argv: Vec<String> = env::args().collect();
-	if argv.len() > 3 {
-		println!("usage: {} [rev] [--show]", argv[0].rsplit('/').next().unwrap());
+	let prog = argv[0].rsplit('/').next().unwrap();
+	if argv.iter().any(|arg| arg == "-h" || arg == "--help") {
+		println!("usage: {} {}", prog, USAGE);
 		return;
 	}
 
 	let repo = match Repository::open_from_env() {
 		Ok(repo) => repo,
 		Err(err) => {
-			println!("{}", err.message());
-			return;
+			eprintln!("{}", err.message());
+			process::exit(1);
 		},
 	};
 	let args = match parse_args(&argv[1..]) {
 		Ok(args) => args,
 		Err(err) => {
-			println!("couldn't get commit: {}", err.message());
-			return;
+			eprintln!("{}\nusage: {} {}", err, prog, USAGE);
+			process::exit(2);
 		},
 	};
 
-	let revwalk = match git::log(&repo, &args.revision_range) {
+	let mut revwalk = match git::log(&repo, &args.revision_range) {
 		Ok(revwalk) => revwalk,
 		Err(err) => {
-			println!("couldn't log {}: {}", args.revision_range, err.message());
-			return;
+			eprintln!("couldn't log {}: {}", args.revision_range, err.message());
+			process::exit(1);
 		},
 	};
 	let decorations = match git::decorations(&repo) {
 		Ok(decorations) => decorations,
 		Err(err) => {
-			println!("couldn't get decorations: {}", err.message());
-			return;
+			eprintln!("couldn't get decorations: {}", err.message());
+			process::exit(1);
+		},
+	};
+
+	if args.line_range.is_some() && (args.format.is_some() || (!args.pick && !io::stdout().is_terminal())) {
+		eprintln!("-L only works interactively. try `git log -L` instead");
+		process::exit(2);
+	}
+
+	if let Some(format) = args.format {
+		if let Err(err) = export::export(&repo, &mut revwalk, &decorations, format, args.pickaxe.as_ref()) {
+			if err.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) {
+				return;
+			}
+			eprintln!("{}", err);
+			process::exit(1);
+		}
+		return;
+	}
+
+	let config = match repo.config() {
+		Ok(config) => config,
+		Err(err) => {
+			eprintln!("couldn't read git config: {}", err.message());
+			process::exit(1);
+		},
+	};
+	let mut settings = match settings::Settings::from_config(&config) {
+		Ok(settings) => settings,
+		Err(err) => {
+			eprintln!("couldn't load settings: {}", err);
+			process::exit(1);
+		},
+	};
+
+	// like git, only color piped output with --color=always
+	let piped = !args.pick && !io::stdout().is_terminal();
+	if args.color == Some(false) || (args.color.is_none() && piped) {
+		settings.theme = theme::Theme::no_color();
+	}
+
+	if piped {
+		let res = print::print_log(
+			&repo,
+			&mut revwalk,
+			&decorations,
+			&args.log_mode,
+			&settings.theme,
+			args.show,
+			args.pickaxe.as_ref(),
+		);
+		if let Err(err) = res {
+			// the reader went away, e.g. `gil | head`
+			if err.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) {
+				return;
+			}
+			eprintln!("{}", err);
+			process::exit(1);
+		}
+		return;
+	}
+
+	let line_history = match args.line_range {
+		Some(ref spec) => match git::line_history(&repo, &args.revision_range, spec, settings.theme.color) {
+			Ok(history) => Some(history),
+			Err(err) => {
+				eprintln!("couldn't get the history of {}: {}", spec, err.message());
+				process::exit(1);
+			},
 		},
+		None => None,
 	};
 
-	let term = terminal::setup().unwrap();
-	let mut app = terminal::App::new(term, &repo, revwalk, decorations, args.revision_range, args.show);
-	let res = app.run_app();
+	install_panic_hook(repo.path().to_owned());
+	let pick = args.pick;
+	let (term, guard) = match terminal::setup(settings.mouse, pick) {
+		Ok(setup) => setup,
+		Err(err) => {
+			eprintln!("couldn't set up the terminal: {}", err);
+			process::exit(1);
+		},
+	};
+	let pickaxe = args.pickaxe.clone();
+	let mut app = terminal::App::new(term, &repo, revwalk, decorations, args, settings);
+	let res = match (line_history, pickaxe) {
+		(Some(history), _) => app.show_line_history(history).map_err(Into::into).and_then(|_| app.run_app()),
+		(None, Some(pickaxe)) => app.start_pickaxe(pickaxe).and_then(|_| app.run_app()),
+		(None, None) => app.run_app(),
+	};
 
-	app.teardown();
-	if let Err(err) = res {
-		println!("{:?}", err)
+	drop(guard);
+	match res {
+		Ok(Some(picked)) => {
+			for commit_id in picked {
+				println!("{}", commit_id);
+			}
+		},
+		Ok(None) => {
+			if pick {
+				process::exit(1);
+			}
+		},
+		Err(err) => {
+			eprintln!("gil: {}\n{}", err, report_context(repo.path()));
+			process::exit(1);
+		},
 	}
 }
 
-struct Args {
-	revision_range: String,
-	show: bool,
+// the terminal has to be restored before the panic message is printed or it's lost with the alternate screen
+fn install_panic_hook(repo_path: PathBuf) {
+	let default_hook = panic::take_hook();
+	panic::set_hook(Box::new(move |info| {
+		terminal::restore();
+		default_hook(info);
+		eprintln!("{}", report_context(&repo_path));
+		eprintln!("please report this at https://github.com/raylu/gil/issues");
+	}));
 }
 
-fn parse_args(args: &[String]) -> Result<Args, git2::Error> {
+fn report_context(repo_path: &Path) -> String {
+	format!("gil {}, repository {}", env!("CARGO_PKG_VERSION"), repo_path.display())
+}
+
+pub struct Args {
+	pub revision_range: String,
+	pub show: bool,
+	pub pick: bool, // print the chosen commit ids to stdout instead of browsing
+	pub log_mode: LogMode,
+	pub color: Option<bool>, // None for auto
+	pub format: Option<export::Format>,
+	pub line_range: Option<String>, // for `git log -L`
+	pub pickaxe: Option<git::Pickaxe>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
 	let mut show = false;
+	let mut pick = false;
+	let mut log_mode = LogMode::Short;
+	let mut color = None;
+	let mut format = None;
+	let mut line_range = None;
+	let mut pickaxe = None;
 	let mut revision_range = None;
-	for arg in args {
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
 		if arg == "--show" {
 			show = true;
+		} else if arg == "--pick" {
+			pick = true;
+		} else if let Some(mode) = arg.strip_prefix("--mode=") {
+			log_mode = match mode {
+				"short" => LogMode::Short,
+				"regular" => LogMode::Medium,
+				"stat" => LogMode::Long,
+				_ => return Err(format!("unknown mode {}", mode)),
+			};
+		} else if let Some(when) = arg.strip_prefix("--color=") {
+			color = match when {
+				"auto" => None,
+				"always" => Some(true),
+				"never" => Some(false),
+				_ => return Err(format!("unknown color setting {}", when)),
+			};
+		} else if let Some(name) = arg.strip_prefix("--format=") {
+			format = Some(export::Format::from_name(name).ok_or_else(|| format!("unknown format {}", name))?);
+		} else if arg == "-L" {
+			line_range = Some(args.next().ok_or("-L needs a line range")?.clone());
+		} else if let Some(range) = arg.strip_prefix("-L") {
+			line_range = Some(range.to_owned());
+		} else if let Some(flag @ ("-S" | "-G")) = arg.get(..2) {
+			let value = match &arg[2..] {
+				"" => args.next().ok_or_else(|| format!("{} needs a value", flag))?.clone(),
+				value => value.to_owned(),
+			};
+			pickaxe = Some(if flag == "-S" {
+				git::Pickaxe::Count(value)
+			} else {
+				git::Pickaxe::Line(regex::Regex::new(&value).map_err(|err| err.to_string())?)
+			});
+		} else if arg.starts_with('-') {
+			return Err(format!("unknown option {}", arg));
 		} else if revision_range.is_none() {
 			revision_range = Some(arg.as_str())
 		} else {
-			return Err(git2::Error::new(
-				git2::ErrorCode::GenericError,
-				git2::ErrorClass::None,
-				format!("cannot pass multiple commits ({})", arg),
-			));
+			return Err(format!("cannot pass multiple commits ({})", arg));
 		}
 	}
+	if line_range.is_some() && pickaxe.is_some() {
+		return Err("-L can't be combined with -S or -G".to_owned());
+	}
 	Ok(Args {
 		revision_range: revision_range.unwrap_or("HEAD").to_string(),
 		show,
+		pick,
+		log_mode,
+		color,
+		format,
+		line_range,
+		pickaxe,
 	})
 }
@@ -0,0 +1,99 @@
+use std::io::{self, Write};
+
+use git2::{Repository, Revwalk};
+use tui::{
+	style::{Color, Modifier, Style},
+	text::Line,
+};
+
+use crate::{
+	git::{next_matching_commit, patch_text, Decorations, LogFilter, Pickaxe},
+	terminal::{commit_info_lines, LogMode},
+	theme::Theme,
+};
+
+// streams the log to stdout when it isn't a terminal, formatted like the interactive log
+pub fn print_log(
+	repo: &Repository,
+	revwalk: &mut Revwalk,
+	decorations: &Decorations,
+	log_mode: &LogMode,
+	theme: &Theme,
+	show_only: bool,
+	pickaxe: Option<&Pickaxe>,
+) -> Result<(), Box<dyn std::error::Error>> {
+	let mut stdout = io::stdout().lock();
+	while let Some(ci) = next_matching_commit(repo, revwalk, &LogFilter::default(), pickaxe)? {
+		// don't wrap; let whatever is reading the output decide
+		for line in commit_info_lines(&ci, log_mode, decorations, theme, false, u16::MAX) {
+			write_line(&mut stdout, &line, theme.color)?;
+		}
+		if show_only {
+			stdout.write_all(patch_text(&ci.patch, None)?.as_bytes())?;
+			break;
+		}
+	}
+	stdout.flush()?;
+	Ok(())
+}
+
+fn write_line(out: &mut impl Write, line: &Line, color: bool) -> io::Result<()> {
+	for span in &line.spans {
+		let style = line.style.patch(span.style);
+		if color && style != Style::default() && !span.content.is_empty() {
+			write!(out, "\x1b[{}m{}\x1b[0m", sgr(style), span.content)?;
+		} else {
+			out.write_all(span.content.as_bytes())?;
+		}
+	}
+	out.write_all(b"\n")
+}
+
+// https://en.wikipedia.org/wiki/ANSI_escape_code#SGR
+fn sgr(style: Style) -> String {
+	let mut params = vec![];
+	for (modifier, param) in [
+		(Modifier::BOLD, "1"),
+		(Modifier::DIM, "2"),
+		(Modifier::ITALIC, "3"),
+		(Modifier::UNDERLINED, "4"),
+		(Modifier::REVERSED, "7"),
+	] {
+		if style.add_modifier.contains(modifier) {
+			params.push(param.to_owned());
+		}
+	}
+	if let Some(fg) = style.fg {
+		params.push(color_param(fg, false));
+	}
+	if let Some(bg) = style.bg {
+		params.push(color_param(bg, true));
+	}
+	params.join(";")
+}
+
+fn color_param(color: Color, bg: bool) -> String {
+	let base = if bg { 40 } else { 30 };
+	let bright = if bg { 100 } else { 90 };
+	match color {
+		Color::Reset => (base + 9).to_string(),
+		Color::Black => base.to_string(),
+		Color::Red => (base + 1).to_string(),
+		Color::Green => (base + 2).to_string(),
+		Color::Yellow => (base + 3).to_string(),
+		Color::Blue => (base + 4).to_string(),
+		Color::Magenta => (base + 5).to_string(),
+		Color::Cyan => (base + 6).to_string(),
+		Color::Gray => (base + 7).to_string(),
+		Color::DarkGray => bright.to_string(),
+		Color::LightRed => (bright + 1).to_string(),
+		Color::LightGreen => (bright + 2).to_string(),
+		Color::LightYellow => (bright + 3).to_string(),
+		Color::LightBlue => (bright + 4).to_string(),
+		Color::LightMagenta => (bright + 5).to_string(),
+		Color::LightCyan => (bright + 6).to_string(),
+		Color::White => (bright + 7).to_string(),
+		Color::Indexed(i) => format!("{};5;{}", base + 8, i),
+		Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+	}
+}
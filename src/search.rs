@@ -0,0 +1,268 @@
+use std::{
+	io::{self, BufRead, BufReader, Read},
+	path::{Path, PathBuf},
+	process::{Child, ChildStdout, Command, Stdio},
+	sync::{
+		mpsc::{self, Receiver, Sender},
+		Arc, Mutex,
+	},
+	thread,
+};
+
+use ansi_to_tui::IntoText;
+use git2::{Oid, Repository};
+use tui::text::Text;
+
+use crate::git::{log, LogFilter, Pickaxe};
+
+// how often the search reports how far it has got
+const PROGRESS_EVERY: usize = 100;
+
+enum SearchEvent {
+	Found(Oid),
+	Progress(usize),
+	Done(usize),
+	Error(String),
+}
+
+// a pickaxe search through the log on another thread, since it has to diff every commit
+pub struct PickaxeSearch {
+	pub pickaxe: Pickaxe,
+	found: Vec<Oid>,
+	next: usize, // the next of `found` to load into the log
+	pub searched: usize,
+	pub done: bool,
+	pub error: Option<String>,
+	events: Receiver<SearchEvent>,
+}
+
+impl PickaxeSearch {
+	// `found` are the matches from the commits up to and including `resume_after`, which have already been searched
+	pub fn start(
+		repo_path: PathBuf,
+		revision_range: String,
+		filter: LogFilter,
+		pickaxe: Pickaxe,
+		found: Vec<Oid>,
+		resume_after: Option<Oid>,
+	) -> PickaxeSearch {
+		let (sender, events) = mpsc::channel();
+		let thread_pickaxe = pickaxe.clone();
+		thread::spawn(move || {
+			let event = match search(
+				&repo_path,
+				&revision_range,
+				&filter,
+				&thread_pickaxe,
+				resume_after,
+				&sender,
+			) {
+				Ok(searched) => SearchEvent::Done(searched),
+				Err(err) => SearchEvent::Error(err.message().to_owned()),
+			};
+			_ = sender.send(event);
+		});
+		PickaxeSearch {
+			pickaxe,
+			searched: 0,
+			found,
+			next: 0,
+			done: false,
+			error: None,
+			events,
+		}
+	}
+
+	// takes in whatever the search has found since the last call
+	pub fn poll(&mut self) {
+		while let Ok(event) = self.events.try_recv() {
+			match event {
+				SearchEvent::Found(commit_id) => self.found.push(commit_id),
+				SearchEvent::Progress(searched) => self.searched = searched,
+				SearchEvent::Done(searched) => {
+					self.searched = searched;
+					self.done = true;
+				},
+				SearchEvent::Error(err) => {
+					self.error = Some(err);
+					self.done = true;
+				},
+			}
+		}
+	}
+
+	// the next match to add to the log, if it has been found yet
+	pub fn next_found(&mut self) -> Option<Oid> {
+		let commit_id = self.found.get(self.next).copied();
+		if commit_id.is_some() {
+			self.next += 1;
+		}
+		commit_id
+	}
+
+	// for loading the log again from the start
+	pub fn reset(&mut self) {
+		self.next = 0;
+	}
+}
+
+// stops early once the receiving end is dropped, i.e. the search was replaced or gil is exiting
+fn search(
+	repo_path: &Path,
+	revision_range: &str,
+	filter: &LogFilter,
+	pickaxe: &Pickaxe,
+	resume_after: Option<Oid>,
+	sender: &Sender<SearchEvent>,
+) -> Result<usize, git2::Error> {
+	// Repository isn't Sync, so this thread needs its own
+	let repo = Repository::open(repo_path)?;
+	let mut skipping = resume_after.is_some();
+	let mut searched = 0;
+	for commit_id in log(&repo, revision_range)? {
+		let commit_id = commit_id?;
+		searched += 1;
+		if skipping {
+			skipping = Some(commit_id) != resume_after;
+			continue;
+		}
+		let commit = repo.find_commit(commit_id)?;
+		if filter.matches(&commit)
+			&& pickaxe.matches_commit(&repo, &commit)?
+			&& sender.send(SearchEvent::Found(commit_id)).is_err()
+		{
+			return Ok(searched);
+		}
+		if searched % PROGRESS_EVERY == 0 && sender.send(SearchEvent::Progress(searched)).is_err() {
+			return Ok(searched);
+		}
+	}
+	Ok(searched)
+}
+
+enum LinesEvent {
+	Found(Oid, Text<'static>),
+	Done,
+	Error(String),
+}
+
+// the commits that changed a range of lines and the hunks for just those lines, read from `git log -L` as it runs,
+// since it can take minutes to get through a long history
+pub struct LineHistory {
+	pub spec: String, // start,end:path or :function:path
+	commits: Vec<(Oid, Text<'static>)>,
+	next: usize, // the next of `commits` to load into the log
+	pub done: bool,
+	pub error: Option<String>,
+	events: Receiver<LinesEvent>,
+	git: Arc<Mutex<Child>>,
+}
+
+impl LineHistory {
+	pub fn start(mut git: Command, spec: String) -> io::Result<LineHistory> {
+		let mut child = git.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+		let stdout = child.stdout.take().unwrap();
+		let git = Arc::new(Mutex::new(child));
+		let (sender, events) = mpsc::channel();
+		let thread_git = Arc::clone(&git);
+		thread::spawn(move || {
+			let event = match read_line_history(stdout, &sender).and_then(|_| finish(&thread_git)) {
+				Ok(()) => LinesEvent::Done,
+				Err(err) => LinesEvent::Error(err.to_string()),
+			};
+			_ = sender.send(event);
+		});
+		Ok(LineHistory {
+			spec,
+			commits: vec![],
+			next: 0,
+			done: false,
+			error: None,
+			events,
+			git,
+		})
+	}
+
+	// takes in whatever git has output since the last call
+	pub fn poll(&mut self) {
+		while let Ok(event) = self.events.try_recv() {
+			match event {
+				LinesEvent::Found(commit_id, hunks) => self.commits.push((commit_id, hunks)),
+				LinesEvent::Done => self.done = true,
+				LinesEvent::Error(err) => {
+					self.error = Some(err);
+					self.done = true;
+				},
+			}
+		}
+	}
+
+	pub fn next_found(&mut self) -> Option<Oid> {
+		let (commit_id, _) = self.commits.get(self.next)?;
+		self.next += 1;
+		Some(*commit_id)
+	}
+
+	// for loading the log again from the start
+	pub fn reset(&mut self) {
+		self.next = 0;
+	}
+
+	pub fn hunks(&self, commit_id: Oid) -> Option<&Text<'static>> {
+		self.commits.iter().find(|(id, _)| *id == commit_id).map(|(_, hunks)| hunks)
+	}
+}
+
+// stops git when the history is replaced or gil is exiting
+impl Drop for LineHistory {
+	fn drop(&mut self) {
+		if let Ok(mut git) = self.git.lock() {
+			_ = git.kill();
+		}
+	}
+}
+
+// each commit is \0, its id, a newline, a blank line and then the hunks
+fn read_line_history(stdout: ChildStdout, sender: &Sender<LinesEvent>) -> io::Result<()> {
+	let mut reader = BufReader::new(stdout);
+	let mut chunk = vec![];
+	loop {
+		chunk.clear();
+		if reader.read_until(0, &mut chunk)? == 0 {
+			return Ok(());
+		}
+		if chunk.last() == Some(&0) {
+			chunk.pop();
+		}
+		if chunk.is_empty() {
+			continue; // before the first commit
+		}
+		let (id, hunks) = match chunk.iter().position(|b| *b == b'\n') {
+			Some(newline) => (&chunk[..newline], &chunk[newline + 1..]),
+			None => (&chunk[..], &[][..]),
+		};
+		let commit_id = Oid::from_str(&String::from_utf8_lossy(id)).map_err(|err| io::Error::other(err.message()))?;
+		let hunks = hunks.strip_prefix(b"\n").unwrap_or(hunks);
+		let hunks = hunks.into_text().unwrap_or_else(|err| Text::raw(format!("ansi_to_tui:\n{}", err)));
+		if sender.send(LinesEvent::Found(commit_id, hunks)).is_err() {
+			return Ok(());
+		}
+	}
+}
+
+// git's own message if it failed, e.g. for a file that doesn't exist
+fn finish(git: &Mutex<Child>) -> io::Result<()> {
+	let mut git = git.lock().unwrap();
+	let status = git.wait()?;
+	if status.success() {
+		return Ok(());
+	}
+	let mut stderr = String::new();
+	if let Some(ref mut pipe) = git.stderr {
+		pipe.read_to_string(&mut stderr)?;
+	}
+	Err(io::Error::other(match stderr.trim() {
+		"" => status.to_string(),
+		message => message.to_owned(),
+	}))
+}
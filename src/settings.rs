@@ -0,0 +1,34 @@
+use git2::Config;
+
+use crate::{clipboard::Clipboard, keymap::Keymap, theme::Theme};
+
+// everything that can be configured through `gil.*` keys in git config
+pub struct Settings {
+	pub keymap: Keymap,
+	pub theme: Theme,
+	pub clipboard: Clipboard,
+	pub mouse: bool,
+	pub hunks_cross_files: bool, // whether the next/previous hunk keys go on into the next/previous file
+}
+
+impl Settings {
+	pub fn from_config(config: &Config) -> Result<Settings, String> {
+		let mouse = get_bool(config, "gil.mouse", true)?;
+		let hunks_cross_files = get_bool(config, "gil.hunks-cross-files", true)?;
+		Ok(Settings {
+			keymap: Keymap::from_config(config)?,
+			theme: Theme::from_config(config)?,
+			clipboard: Clipboard::from_config(config)?,
+			mouse,
+			hunks_cross_files,
+		})
+	}
+}
+
+fn get_bool(config: &Config, name: &str, default: bool) -> Result<bool, String> {
+	match config.get_bool(name) {
+		Ok(value) => Ok(value),
+		Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(default),
+		Err(err) => Err(err.message().to_owned()),
+	}
+}
@@ -1,31 +1,57 @@
 use crossterm::{
+	cursor::Show,
 	event::{
-		self, Event,
-		KeyCode::{self, Char},
-		KeyEvent,
+		self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent,
+		MouseEventKind,
 	},
 	execute,
 	terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use git2::{BranchType, Repository, Revwalk};
+use git2::{BranchType, Oid, Repository, Revwalk};
+use regex::Regex;
 use std::{
+	collections::{hash_map::RandomState, HashMap, HashSet},
+	env,
 	error::Error,
-	io::{self, Stdout},
-	os::unix::process::CommandExt,
-	process::Command,
+	fs::{self, OpenOptions},
+	hash::BuildHasher,
+	io::{self, Write},
+	mem,
+	ops::Range,
+	os::unix::{fs::DirBuilderExt, process::CommandExt},
+	path::{Path, PathBuf},
+	process::{self, Command},
+	sync::atomic::{AtomicBool, Ordering},
+	time::{Duration, Instant},
 };
 use tui::{
 	backend::CrosstermBackend,
-	layout::{Constraint, Direction, Layout, Rect, Size},
-	style::{Color, Style, Stylize as _},
+	layout::{Constraint, Direction, Layout, Margin, Position, Rect, Size},
+	style::Style,
 	text::{Line, Span, Text, ToSpan as _},
 	widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 	Frame, Terminal,
 };
 
-use crate::git::{next_commit, show, CommitInfo, Decorations};
+use crate::{
+	clipboard::Clipboard,
+	file_list::{FileList, FileSort},
+	git::{
+		commit_info, file_at, file_stats, first_hunk_range, format_patches, git_command, hunk_headers, line_history,
+		log, log_position, new_line_number, next_file_commit, next_line_commit, next_matching_commit, patch_text,
+		set_diff, show, CommitInfo, Decorations, DiffMode, FileAt, FileHistory, FilterField, LogFilter, PatchOptions,
+		Pickaxe,
+	},
+	keymap::{Action, Keymap},
+	search::{LineHistory, PickaxeSearch},
+	settings::Settings,
+	theme::Theme,
+	Args,
+};
 
-type CrosstermTerm = Terminal<CrosstermBackend<Stdout>>;
+const SEARCH_REDRAW: Duration = Duration::from_millis(100);
+
+type CrosstermTerm = Terminal<CrosstermBackend<Box<dyn Write>>>;
 
 pub struct App<'repo> {
 	term: CrosstermTerm,
@@ -33,57 +59,580 @@ pub struct App<'repo> {
 	revwalk: Revwalk<'repo>,
 	revision_range: String,
 	show_only: bool,
+	pick: bool,
+	mouse: bool,
+	hunks_cross_files: bool,
+	whole_commit: bool, // show every file of a commit at once
+	file_content: FileContent,
+	file_sort: FileSort,
+	patch_options: PatchOptions,
+	keymap: Keymap,
+	clipboard: Clipboard,
+	yank_pending: bool,
+	filter_pending: bool,
+	diff_pending: bool,
+	count: Option<usize>,             // digits typed before an action, like vim
+	children: HashMap<Oid, Vec<Oid>>, // of the commits loaded so far, in log order
+	history: Vec<Oid>,                // commits viewed before following a parent or child
+	forward: Vec<Oid>,                // commits left by going back
+	picked: Option<Vec<Oid>>,
 	state: AppRenderState<'repo>,
 }
 
 struct AppRenderState<'repo> {
 	commit_infos: Vec<CommitInfo<'repo>>,
 	decorations: Decorations,
+	filter: LogFilter,
+	scope: Option<LogScope>,
 	log_mode: LogMode,
 	log_state: ListState,
-	commit_view: Option<CommitView>,
+	commit_view: Option<CommitView<'repo>>,
+	marked: HashSet<Oid>,
 	popup: Option<Text<'static>>,
+	prompt: Option<Prompt>,
+	status: Option<String>, // shown in the bottom line until the next key press
+	theme: Theme,
+	areas: PaneAreas,
+	cap_split: Option<u16>, // percentage of the commit view taken by the message and files, set by dragging
+	mouse: MouseState,
+}
+
+// limits the log to the commits that changed a file or some lines
+enum LogScope {
+	File(FileHistory),
+	Lines(LineHistory),
+	Pickaxe(PickaxeSearch),
 }
 
-struct CommitView {
-	index: usize,
+impl LogScope {
+	// whether more commits may still turn up in the background
+	fn searching(&self) -> bool {
+		match self {
+			LogScope::File(_) => false,
+			LogScope::Lines(history) => !history.done,
+			LogScope::Pickaxe(search) => !search.done,
+		}
+	}
+}
+
+// screen areas from the last draw, used to map mouse events to panes
+#[derive(Default)]
+struct PaneAreas {
+	log: Rect,
+	cap: Rect, // message and files, or the whole commit view when there is no patch
+	cap_direction: Direction,
+	message: Rect,
+	files: Rect,
+	patch: Rect,
+}
+
+#[derive(Default)]
+struct MouseState {
+	dragging_split: bool,
+	selecting_lines: bool,
+	last_click: Option<(Instant, Position)>,
+}
+
+struct CommitView<'repo> {
+	commit: ViewedCommit<'repo>,
 	message_scroll: u16,
-	files_state: ListState,
+	files: FileList,
 	file_view: Option<FileView>,
+	whole: bool, // file_view has every file, and the file list follows it
+	content: FileContent,
+}
+
+// what the file pane shows for the selected file
+#[derive(Clone, Copy, PartialEq)]
+enum FileContent {
+	Patch,
+	New, // the whole file as of the commit
+	Old, // the whole file as of the parent
+}
+
+impl FileContent {
+	fn next(self) -> FileContent {
+		match self {
+			FileContent::Patch => FileContent::New,
+			FileContent::New => FileContent::Old,
+			FileContent::Old => FileContent::Patch,
+		}
+	}
+
+	fn description(self) -> Option<&'static str> {
+		match self {
+			FileContent::Patch => None,
+			FileContent::New => Some("file at this commit"),
+			FileContent::Old => Some("file at parent"),
+		}
+	}
+}
+
+enum ViewedCommit<'repo> {
+	Log(usize),
+	// reached by following parents but not part of the log, e.g. outside the revision range, or not found by a
+	// search yet
+	Other(Box<CommitInfo<'repo>>),
+}
+
+impl<'repo> ViewedCommit<'repo> {
+	fn get<'a>(&'a self, commit_infos: &'a [CommitInfo<'repo>]) -> &'a CommitInfo<'repo> {
+		match self {
+			ViewedCommit::Log(index) => &commit_infos[*index],
+			ViewedCommit::Other(commit) => commit,
+		}
+	}
+
+	fn get_mut<'a>(&'a mut self, commit_infos: &'a mut [CommitInfo<'repo>]) -> &'a mut CommitInfo<'repo> {
+		match self {
+			ViewedCommit::Log(index) => &mut commit_infos[*index],
+			ViewedCommit::Other(commit) => commit,
+		}
+	}
+
+	fn log_index(&self) -> Option<usize> {
+		match self {
+			ViewedCommit::Log(index) => Some(*index),
+			ViewedCommit::Other(_) => None,
+		}
+	}
+}
+
+// a line of text input at the bottom of the screen
+struct Prompt {
+	kind: PromptKind,
+	input: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PromptKind {
+	PatchDir,
+	Jump,
+	Filter(FilterField),
+	Lines,
+	Pickaxe,
+	PickaxeRegex,
+	Search,
+	FilterFiles,
+	EditCopy,
+	RenameThreshold,
+	CopyThreshold,
+}
+
+impl PromptKind {
+	fn label(self) -> &'static str {
+		match self {
+			PromptKind::PatchDir => "write patches to: ",
+			PromptKind::Jump => "go to: ",
+			PromptKind::Filter(FilterField::Author) => "author regex: ",
+			PromptKind::Filter(FilterField::Committer) => "committer regex: ",
+			PromptKind::Filter(FilterField::Since) => "since (YYYY-MM-DD or 2 weeks ago): ",
+			PromptKind::Filter(FilterField::Until) => "until (YYYY-MM-DD or 2 weeks ago): ",
+			PromptKind::Filter(FilterField::Grep) => "message regex: ",
+			PromptKind::Lines => "lines (start,end or :function): ",
+			PromptKind::Pickaxe => "commits that add or remove: ",
+			PromptKind::PickaxeRegex => "commits with a changed line matching: ",
+			PromptKind::Search => "/",
+			PromptKind::FilterFiles => "files matching: ",
+			PromptKind::EditCopy => "not in the working tree. edit a copy from this commit? [y/N] ",
+			PromptKind::RenameThreshold => "rename threshold percent (empty for no renames): ",
+			PromptKind::CopyThreshold => "copy threshold percent (empty for no copies): ",
+		}
+	}
 }
 
 struct FileView {
 	contents: Text<'static>,
 	scroll: u16,
+	search: Option<FileSearch>,
+	hunks: Vec<usize>,                 // the line index of each hunk header
+	files: Vec<usize>,                 // the line index where each file starts, when showing the whole commit
+	full_file: bool,                   // the whole file with line numbers rather than a patch
+	rows: Option<(u16, Vec<u16>)>,     // the width and the row that each line starts on, from `line_rows`
+	selection: Option<(usize, usize)>, // where a selection of lines in a full file started and ended, for `L`
 }
 
-impl App<'_> {
+struct FileSearch {
+	matches: Vec<(usize, Range<usize>)>, // line index and byte range within that line
+	current: usize,
+}
+
+impl FileView {
+	// `headers` from `hunk_headers` are found in order. without them, or if any of them isn't there (git's diff.algorithm
+	// can split the hunks differently than libgit2), falls back to every line that is a hunk header
+	fn new(contents: Text<'static>, headers: &[String]) -> FileView {
+		let texts: Vec<String> = contents.lines.iter().map(line_text).collect();
+		let mut hunks = vec![];
+		let mut next_line = 0;
+		for header in headers {
+			if let Some(offset) = texts[next_line..].iter().position(|text| text.contains(header.as_str())) {
+				hunks.push(next_line + offset);
+				next_line += offset + 1;
+			} else {
+				hunks.clear();
+				break;
+			}
+		}
+		if hunks.is_empty() {
+			hunks = (0..texts.len()).filter(|&i| hunk_header_line(&texts[i]).is_some()).collect();
+		}
+		FileView {
+			contents,
+			scroll: 0,
+			search: None,
+			hunks,
+			files: vec![],
+			full_file: false,
+			rows: None,
+			selection: None,
+		}
+	}
+
+	// finds where each file starts in the output for the whole commit from its old and new paths: its `diff` header, or
+	// failing that (with a pager like delta) the next line with its path. files that can't be found start where the one
+	// before did
+	fn with_files(mut self, paths: &[(String, String)]) -> FileView {
+		let texts: Vec<String> = self.contents.lines.iter().map(line_text).collect();
+		let mut start = 0;
+		let mut next_line = 0;
+		for (old_path, new_path) in paths {
+			// merges have combined diffs
+			let headers = [
+				format!("diff --git a/{} b/{}", old_path, new_path),
+				format!("diff --cc {}", new_path),
+				format!("diff --combined {}", new_path),
+			];
+			let rest = &texts[next_line..];
+			let offset = rest
+				.iter()
+				.position(|text| headers.contains(text))
+				.or_else(|| rest.iter().position(|text| text.contains(new_path.as_str())));
+			if let Some(offset) = offset {
+				start = next_line + offset;
+				next_line = start + 1;
+			}
+			self.files.push(start);
+		}
+		self
+	}
+
+	// the last file that starts at or above the top of the pane
+	fn current_file(&mut self, width: u16) -> Option<usize> {
+		if self.files.is_empty() {
+			return None;
+		}
+		let top = self.scroll;
+		self.line_rows(width);
+		let rows = &self.rows.as_ref().unwrap().1;
+		Some(self.files.iter().rposition(|&line| rows[line] <= top).unwrap_or(0))
+	}
+
+	fn scroll_to_file(&mut self, index: usize, width: u16) {
+		if let Some(&line) = self.files.get(index) {
+			self.scroll = self.line_rows(width)[line];
+		}
+	}
+
+	// case insensitive unless the query has an uppercase letter. starts from the first match at or below the top of
+	// the pane, which is `width` by `height` inside its borders
+	fn search(&mut self, query: &str, width: u16, height: u16) -> Result<(), regex::Error> {
+		self.search = None;
+		let mut pattern = regex::escape(query);
+		if !query.chars().any(char::is_uppercase) {
+			pattern.insert_str(0, "(?i)");
+		}
+		let regex = Regex::new(&pattern)?;
+		let mut matches = vec![];
+		for (line_index, line) in self.contents.lines.iter().enumerate() {
+			matches.extend(regex.find_iter(&line_text(line)).map(|m| (line_index, m.range())));
+		}
+		if matches.is_empty() {
+			return Ok(());
+		}
+		let top = self.scroll;
+		let rows = self.line_rows(width);
+		let current = matches.iter().position(|(line, _)| rows[*line] >= top).unwrap_or(0);
+		self.search = Some(FileSearch { matches, current });
+		self.scroll_to_match(width, height);
+		Ok(())
+	}
+
+	// wraps around at either end
+	fn step_match(&mut self, amount: isize, width: u16, height: u16) {
+		if let Some(ref mut search) = self.search {
+			let len = search.matches.len() as isize;
+			search.current = (search.current as isize + amount).rem_euclid(len) as usize;
+			self.scroll_to_match(width, height);
+		}
+	}
+
+	fn scroll_to_match(&mut self, width: u16, height: u16) {
+		let Some(ref search) = self.search else {
+			return;
+		};
+		let (line, _) = search.matches[search.current];
+		let top = self.scroll;
+		let row = self.line_rows(width)[line];
+		if row < top || row >= top.saturating_add(height) {
+			self.scroll = row.saturating_sub(height / 3);
+		}
+	}
+
+	// the row that each line starts on once wrapped to `width`
+	fn line_rows(&mut self, width: u16) -> &[u16] {
+		if self.rows.as_ref().is_none_or(|(rows_width, _)| *rows_width != width) {
+			let mut row: u16 = 0;
+			let mut rows = Vec::with_capacity(self.contents.lines.len());
+			for line in &self.contents.lines {
+				rows.push(row);
+				let height = Paragraph::new(line.clone()).wrap(Wrap { trim: false }).line_count(width);
+				row = row.saturating_add(height.try_into().unwrap_or(u16::MAX));
+			}
+			self.rows = Some((width, rows));
+		}
+		&self.rows.as_ref().unwrap().1
+	}
+
+	// the last hunk that starts at or above the top of the pane
+	fn current_hunk(&mut self, width: u16) -> Option<usize> {
+		let top = self.scroll;
+		self.hunk_rows(width).iter().rposition(|&row| row <= top)
+	}
+
+	fn hunk_rows(&mut self, width: u16) -> Vec<u16> {
+		self.line_rows(width);
+		let rows = &self.rows.as_ref().unwrap().1;
+		self.hunks.iter().map(|&line| rows[line]).collect()
+	}
+
+	// the line number that the hunk at the top of the pane (or the first hunk) starts at in the file, from its @@ header
+	// or the full file's line numbers
+	fn hunk_line(&mut self, width: u16) -> Option<u32> {
+		let hunk = self.current_hunk(width).unwrap_or(0);
+		let &line = self.hunks.get(hunk)?;
+		if self.full_file {
+			return u32::try_from(line + 1).ok();
+		}
+		hunk_header_line(&line_text(&self.contents.lines[line]))
+	}
+
+	fn hunk_status(&mut self, width: u16) -> Option<String> {
+		let total = self.hunks.len();
+		match self.current_hunk(width) {
+			_ if total == 0 => None,
+			Some(hunk) => Some(format!("hunk {}/{}", hunk + 1, total)),
+			None if total == 1 => Some("1 hunk".to_owned()),
+			None => Some(format!("{} hunks", total)),
+		}
+	}
+
+	// scrolls the next or previous hunk to the top. false if there isn't one in that direction
+	fn step_hunk(&mut self, forward: bool, width: u16) -> bool {
+		let top = self.scroll;
+		let mut hunk_rows = self.hunk_rows(width).into_iter();
+		let row = if forward {
+			hunk_rows.find(|&row| row > top)
+		} else {
+			hunk_rows.rev().find(|&row| row < top)
+		};
+		if let Some(row) = row {
+			self.scroll = row;
+		}
+		row.is_some()
+	}
+
+	fn scroll_to_hunk(&mut self, first: bool, width: u16) {
+		let line = if first { self.hunks.first() } else { self.hunks.last() };
+		if let Some(&line) = line {
+			self.scroll = self.line_rows(width)[line];
+		}
+	}
+
+	// the line drawn at `row` of the pane
+	fn line_at(&mut self, row: u16, width: u16) -> Option<usize> {
+		let row = self.scroll.saturating_add(row);
+		self.line_rows(width).partition_point(|&start| start <= row).checked_sub(1)
+	}
+
+	// starts a selection at `line`, or moves the end of the current one there
+	fn select_line(&mut self, line: usize, extend: bool) {
+		self.selection = match self.selection {
+			Some((start, _)) if extend => Some((start, line)),
+			_ => Some((line, line)),
+		};
+	}
+
+	// for a click or drag on `row` of the pane. false if this isn't a full file, which is all that can be selected
+	fn select_row(&mut self, row: u16, width: u16, extend: bool) -> bool {
+		if !self.full_file {
+			return false;
+		}
+		if let Some(line) = self.line_at(row, width) {
+			self.select_line(line, extend);
+		}
+		true
+	}
+
+	// the first and last line numbers of the selection
+	fn selected_lines(&self) -> Option<(usize, usize)> {
+		let (start, end) = self.selection?;
+		Some((start.min(end) + 1, start.max(end) + 1))
+	}
+
+	fn selection_status(&self) -> Option<String> {
+		match self.selected_lines()? {
+			(first, last) if first == last => Some(format!("line {} selected", first)),
+			(first, last) => Some(format!("lines {}-{} selected", first, last)),
+		}
+	}
+
+	fn match_status(&self) -> Option<String> {
+		let search = self.search.as_ref()?;
+		Some(format!("match {}/{}", search.current + 1, search.matches.len()))
+	}
+
+	fn highlighted(&self, theme: &Theme) -> Text<'static> {
+		let mut contents = self.contents.clone();
+		if let Some((first, last)) = self.selected_lines() {
+			for line in &mut contents.lines[first - 1..last] {
+				*line = mem::take(line).patch_style(theme.highlight);
+			}
+		}
+		if let Some(ref search) = self.search {
+			for (i, (line_index, range)) in search.matches.iter().enumerate() {
+				let style = if i == search.current {
+					theme.search_current
+				} else {
+					theme.search_match
+				};
+				let line = &mut contents.lines[*line_index];
+				line.spans = highlight_range(mem::take(&mut line.spans), range, style);
+			}
+		}
+		contents
+	}
+}
+
+// only readable by the user and with a name that can't be guessed, since the temp dir is usually shared
+fn private_temp_dir() -> io::Result<PathBuf> {
+	loop {
+		let dir = env::temp_dir().join(format!("gil-{:016x}", RandomState::new().hash_one(process::id())));
+		match fs::DirBuilder::new().mode(0o700).create(&dir) {
+			Ok(()) => return Ok(dir),
+			Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+			Err(err) => return Err(err),
+		}
+	}
+}
+
+fn line_text(line: &Line) -> String {
+	line.spans.iter().map(|span| span.content.as_ref()).collect()
+}
+
+// the line in the new file that a hunk header starts at: `@@ -1,2 +1,3 @@`, or `@@@ -1,2 -1,2 +1,3 @@@` for a combined
+// diff, followed by anything. delta drops the +/- from the patch lines, so a line that merely starts with @@ could be
+// from the file
+fn hunk_header_line(text: &str) -> Option<u32> {
+	let ats = text.bytes().take_while(|&b| b == b'@').count();
+	if ats < 2 {
+		return None;
+	}
+	let mut words = text[ats..].split(' ');
+	if words.next() != Some("") {
+		return None;
+	}
+	let range_start = |word: &str, sign: char| -> Option<u32> {
+		let range = word.strip_prefix(sign)?;
+		let (start, len) = range.split_once(',').unwrap_or((range, "0"));
+		let numeric = |n: &str| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit());
+		(numeric(start) && numeric(len)).then(|| start.parse().ok())?
+	};
+	for _ in 1..ats {
+		range_start(words.next()?, '-')?;
+	}
+	let line = range_start(words.next()?, '+')?;
+	(words.next() == Some(&text[..ats])).then_some(line)
+}
+
+// splits the spans that `range` covers so that just that part gets `style` on top of its own
+fn highlight_range(spans: Vec<Span<'static>>, range: &Range<usize>, style: Style) -> Vec<Span<'static>> {
+	let mut highlighted = Vec::with_capacity(spans.len() + 2);
+	let mut start = 0;
+	for span in spans {
+		let end = start + span.content.len();
+		if end <= range.start || start >= range.end {
+			highlighted.push(span);
+		} else {
+			let content = span.content.as_ref();
+			let from = range.start.saturating_sub(start);
+			let to = (range.end - start).min(content.len());
+			if from > 0 {
+				highlighted.push(Span::styled(content[..from].to_owned(), span.style));
+			}
+			highlighted.push(Span::styled(content[from..to].to_owned(), span.style.patch(style)));
+			if to < content.len() {
+				highlighted.push(Span::styled(content[to..].to_owned(), span.style));
+			}
+		}
+		start = end;
+	}
+	highlighted
+}
+
+impl<'repo> App<'repo> {
 	pub fn new<'a>(
 		term: CrosstermTerm,
 		repo: &'a Repository,
 		revwalk: Revwalk<'a>,
 		decorations: Decorations,
-		revision_range: String,
-		show_only: bool,
+		args: Args,
+		settings: Settings,
 	) -> App<'a> {
 		App {
 			term,
 			repo,
 			revwalk,
-			revision_range,
-			show_only,
+			revision_range: args.revision_range,
+			show_only: args.show,
+			pick: args.pick,
+			mouse: settings.mouse,
+			hunks_cross_files: settings.hunks_cross_files,
+			whole_commit: false,
+			file_content: FileContent::Patch,
+			file_sort: FileSort::Path,
+			patch_options: PatchOptions::default(),
+			keymap: settings.keymap,
+			clipboard: settings.clipboard,
+			yank_pending: false,
+			filter_pending: false,
+			diff_pending: false,
+			count: None,
+			children: HashMap::new(),
+			history: vec![],
+			forward: vec![],
+			picked: None,
 			state: AppRenderState {
 				commit_infos: vec![],
 				decorations,
-				log_mode: LogMode::Short,
+				filter: LogFilter::default(),
+				scope: None,
+				log_mode: args.log_mode,
 				log_state: ListState::default(),
 				commit_view: None,
+				marked: HashSet::new(),
 				popup: None,
+				prompt: None,
+				status: None,
+				theme: settings.theme,
+				areas: PaneAreas::default(),
+				cap_split: None,
+				mouse: MouseState::default(),
 			},
 		}
 	}
 
-	pub fn run_app(&mut self) -> Result<(), Box<dyn Error>> {
+	// returns the picked commits in --pick mode
+	pub fn run_app(&mut self) -> Result<Option<Vec<Oid>>, Box<dyn Error>> {
 		loop {
 			let needed = if self.show_only {
 				1
@@ -91,92 +640,784 @@ impl App<'_> {
 				let commits_per_window = usize::from(self.term.size()?.height / 2);
 				commits_per_window + self.state.log_state.selected().unwrap_or_default()
 			};
-			while self.state.commit_infos.len() < needed {
-				let commit_info = match next_commit(self.repo, &mut self.revwalk) {
-					Ok(None) => break,
-					Ok(Some(ci)) => ci,
-					Err(err) => {
-						self.state.popup = Some(err.message().to_owned().into());
-						break;
-					},
-				};
-				self.state.commit_infos.push(commit_info);
-			}
+			self.load_commits(needed);
 
 			if self.show_only && self.state.commit_view.is_none() {
 				self.show_commit(0);
 			}
 
 			self.term.draw(|frame| ui(frame, &mut self.state))?;
-			if let Event::Key(key) = event::read()? {
-				match handle_input(&key, self, &self.term.size()?) {
+			if self.searching() && !event::poll(SEARCH_REDRAW)? {
+				// draw what the search has found so far
+				continue;
+			}
+			match event::read()? {
+				Event::Key(key) => match handle_input(&key, self, &self.term.size()?) {
 					Ok(false) => {
-						return Ok(());
+						return Ok(self.picked.take());
 					},
 					Ok(true) => {}, // ignored
 					Err(err) => self.state.popup = Some(format!("{}", err).into()),
+				},
+				Event::Mouse(mouse) => handle_mouse(&mouse, self, &self.term.size()?),
+				Event::Resize(_, _) => {
+					// the next iteration loads enough commits for the new height
+					self.term.autoresize()?;
+					if let Some(ref mut show_commit) = self.state.commit_view {
+						scroll_file(&mut show_commit.file_view, &self.term.size()?, 0);
+					}
+				},
+				_ => {}, // ignored
+			}
+		}
+	}
+
+	fn load_commits(&mut self, needed: usize) {
+		match self.state.scope {
+			Some(LogScope::Lines(ref mut history)) => {
+				history.poll();
+				if let Some(err) = history.error.take() {
+					self.state.popup = Some(format!("git log -L failed: {}", err).into());
 				}
+			},
+			Some(LogScope::Pickaxe(ref mut search)) => {
+				search.poll();
+				if let Some(err) = search.error.take() {
+					self.state.popup = Some(format!("search failed: {}", err).into());
+				}
+			},
+			_ => {},
+		}
+		while self.state.commit_infos.len() < needed {
+			let next = match self.state.scope {
+				Some(LogScope::File(ref mut history)) => {
+					next_file_commit(self.repo, &mut self.revwalk, &self.state.filter, history)
+				},
+				Some(LogScope::Lines(ref mut history)) => next_line_commit(self.repo, &self.state.filter, history),
+				Some(LogScope::Pickaxe(ref mut search)) => match search.next_found() {
+					Some(commit_id) => commit_info(self.repo, commit_id).map(Some),
+					None => Ok(None), // or not found yet
+				},
+				None => next_matching_commit(self.repo, &mut self.revwalk, &self.state.filter, None),
+			};
+			let commit_info = match next {
+				Ok(None) => break,
+				Ok(Some(ci)) => ci,
+				Err(err) => {
+					self.state.popup = Some(err.message().to_owned().into());
+					break;
+				},
+			};
+			for parent_id in &commit_info.parent_ids {
+				self.children.entry(*parent_id).or_default().push(commit_info.commit_id);
 			}
+			let index = self.state.commit_infos.len();
+			let commit_info = match self.state.commit_view {
+				// it was opened before it was loaded, so attach it to the log, keeping its diff mode and options
+				Some(ref mut show_commit) if matches!(show_commit.commit, ViewedCommit::Other(ref commit) if commit.commit_id == commit_info.commit_id) =>
+				{
+					self.state.log_state.select(Some(index));
+					match mem::replace(&mut show_commit.commit, ViewedCommit::Log(index)) {
+						ViewedCommit::Other(commit) => *commit,
+						ViewedCommit::Log(_) => commit_info,
+					}
+				},
+				_ => commit_info,
+			};
+			self.state.commit_infos.push(commit_info);
 		}
 	}
 
-	pub fn teardown(&mut self) {
-		_ = disable_raw_mode();
-		_ = execute!(self.term.backend_mut(), LeaveAlternateScreen);
-		_ = self.term.show_cursor();
+	// what ctrl-z would do outside of raw mode
+	fn suspend(&mut self) -> Result<(), Box<dyn Error>> {
+		restore();
+		// stop the whole process group like the terminal would, so that `gil --pick | xargs ...` stops too
+		unsafe { libc::kill(0, libc::SIGTSTP) };
+		// and carry on here after SIGCONT, e.g. from `fg`
+		enter(self.term.backend_mut(), self.mouse)?;
+		self.term.clear()?;
+		Ok(())
+	}
+
+	// opens the selected file in the working tree, or offers a copy from the commit if it's not there anymore
+	fn edit_selected_file(&mut self) -> Result<(), Box<dyn Error>> {
+		let Some((path, _, line)) = self.selected_file_line() else {
+			return Ok(());
+		};
+		match self.repo.workdir().map(|workdir| workdir.join(&path)) {
+			Some(file) if file.exists() => self.run_editor(&file, line)?,
+			_ => self.prompt(PromptKind::EditCopy, ""),
+		}
+		Ok(())
+	}
+
+	// the path, blob and line to edit for the selected file: its new side unless the commit deleted it
+	fn selected_file_line(&mut self) -> Option<(PathBuf, Oid, u32)> {
+		let (width, _) = self.patch_size();
+		let show_commit = self.state.commit_view.as_mut()?;
+		let commit = show_commit.commit.get(&self.state.commit_infos);
+		let index = show_commit.files.current()?;
+		let delta = commit.patch.get_delta(index)?;
+		let deleted = delta.new_file().id().is_zero();
+		let file = if deleted { delta.old_file() } else { delta.new_file() };
+		let path = file.path()?.to_owned();
+		let line = show_commit.file_view.as_mut().and_then(|file_view| file_view.hunk_line(width)).unwrap_or(1);
+		if show_commit.content == FileContent::Old && !deleted {
+			// the line numbers are the parent's
+			return Some((
+				path,
+				file.id(),
+				new_line_number(&commit.patch, index, line).unwrap_or(line),
+			));
+		}
+		Some((path, file.id(), line))
+	}
+
+	// $VISUAL or $EDITOR, run through the shell like git does so that it can have arguments
+	fn run_editor(&mut self, file: &Path, line: u32) -> Result<(), Box<dyn Error>> {
+		let editor = ["VISUAL", "EDITOR"]
+			.iter()
+			.filter_map(|name| env::var(name).ok())
+			.find(|editor| !editor.is_empty())
+			.unwrap_or_else(|| "vi".to_owned());
+		let mut command = Command::new("sh");
+		command.arg("-c").arg(format!("{} \"$@\"", editor)).arg(&editor).arg(format!("+{}", line)).arg(file);
+		if let Some(workdir) = self.repo.workdir() {
+			command.current_dir(workdir);
+		}
+		if ON_TTY.load(Ordering::SeqCst) {
+			// stdout is for the picked commits
+			let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+			command.stdin(tty.try_clone()?).stdout(tty);
+		}
+
+		restore();
+		let status = command.status();
+		enter(self.term.backend_mut(), self.mouse)?;
+		self.term.clear()?;
+		let status = status?;
+		if !status.success() {
+			self.state.status = Some(format!("{} exited with {}", editor, status));
+		}
+		Ok(())
+	}
+
+	// starts the log over, e.g. after the filter changes
+	fn reload_log(&mut self) -> Result<(), git2::Error> {
+		self.revwalk = log(self.repo, &self.revision_range)?;
+		match self.state.scope {
+			Some(LogScope::File(ref mut history)) => history.reset(),
+			Some(LogScope::Lines(ref mut history)) => history.reset(),
+			Some(LogScope::Pickaxe(ref mut search)) => search.reset(),
+			None => {},
+		}
+		self.state.commit_infos.clear();
+		self.children.clear();
+		self.state.log_state = ListState::default();
+		Ok(())
 	}
 
 	fn show_commit(&mut self, index: usize) {
+		self.view_commit(ViewedCommit::Log(index));
+	}
+
+	fn view_commit(&mut self, commit: ViewedCommit<'repo>) {
+		self.close_commit_view();
 		self.state.commit_view = Some(CommitView {
-			index,
+			commit,
 			message_scroll: 0,
-			files_state: ListState::default(),
+			files: FileList::new(self.file_sort),
 			file_view: None,
+			whole: self.whole_commit,
+			content: self.file_content,
 		});
+		if let Err(err) = self.apply_patch_options() {
+			self.state.popup = Some(err.message().to_owned().into());
+		}
+		self.show_first_file();
+	}
+
+	// the diff options only apply to the open commit, so the log goes back to counting its changes with the defaults
+	fn close_commit_view(&mut self) {
+		let Some(ViewedCommit::Log(index)) = self.state.commit_view.take().map(|show_commit| show_commit.commit) else {
+			return;
+		};
+		let Some(commit) = self.state.commit_infos.get_mut(index) else {
+			return;
+		};
+		if commit.patch_options != PatchOptions::default() {
+			if let Err(err) = set_diff(self.repo, commit, commit.diff_mode, &PatchOptions::default()) {
+				self.state.popup = Some(err.message().to_owned().into());
+			}
+		}
+	}
+
+	// recomputes the viewed commit's patch if it was computed with other options
+	fn apply_patch_options(&mut self) -> Result<(), git2::Error> {
+		let show_commit = self.state.commit_view.as_mut().unwrap();
+		let commit = show_commit.commit.get_mut(&mut self.state.commit_infos);
+		if commit.patch_options != self.patch_options {
+			set_diff(self.repo, commit, commit.diff_mode, &self.patch_options)?;
+		}
+		Ok(())
+	}
 
-		let commit = &self.state.commit_infos[index];
+	// keeps the selected file selected if it's still in the patch
+	fn patch_options_changed(&mut self) -> Result<(), git2::Error> {
+		let show_commit = self.state.commit_view.as_ref().unwrap();
+		let commit = show_commit.commit.get(&self.state.commit_infos);
+		let delta = show_commit.files.current().and_then(|index| commit.patch.get_delta(index));
+		let path = delta.and_then(|delta| delta.new_file().path().map(Path::to_owned));
+		let message_scroll = show_commit.message_scroll;
+		self.apply_patch_options()?;
+		self.show_first_file();
+
+		let show_commit = self.state.commit_view.as_mut().unwrap();
+		show_commit.message_scroll = message_scroll;
+		let commit = show_commit.commit.get(&self.state.commit_infos);
+		let index =
+			path.and_then(|path| commit.patch.deltas().position(|delta| delta.new_file().path() == Some(&path)));
+		if let Some(index) = index.filter(|&index| show_commit.files.selected() != Some(index)) {
+			if show_commit.files.select(index) {
+				self.show_commit_file(index);
+			}
+		}
+		self.state.status = Some(self.patch_options.description());
+		Ok(())
+	}
+
+	// or the file whose history is being shown, or the hunks for the lines whose history is being shown
+	fn show_first_file(&mut self) {
+		let show_commit = self.state.commit_view.as_mut().unwrap();
+		show_commit.file_view = None;
+		let commit = show_commit.commit.get(&self.state.commit_infos);
+		show_commit.files.load(file_stats(&commit.patch).unwrap_or_default());
+		if let Some(LogScope::Lines(ref history)) = self.state.scope {
+			if let Some(hunks) = history.hunks(commit.commit_id) {
+				show_commit.file_view = Some(FileView::new(hunks.clone(), &[]));
+				return;
+			}
+		}
 		if commit.patch.deltas().len() > 0 {
-			// immediately show the first file
-			self.state.commit_view.as_mut().unwrap().files_state.select_first();
-			self.show_commit_file(0);
+			let path = match self.state.scope {
+				Some(LogScope::File(ref history)) => history.path_at(commit.commit_id),
+				_ => None,
+			};
+			let index = match path {
+				Some(path) => commit.patch.deltas().position(|delta| delta.new_file().path() == Some(path)),
+				None if show_commit.whole => Some(0), // the top of the patch
+				None => show_commit.files.first_file(),
+			};
+			if let Some(index) = index {
+				show_commit.files.select(index);
+				self.show_commit_file(index);
+			}
+		}
+	}
+
+	fn show_file_history(&mut self, path: PathBuf) -> Result<(), git2::Error> {
+		self.set_scope(LogScope::File(FileHistory::new(path)))
+	}
+
+	pub fn show_line_history(&mut self, history: LineHistory) -> Result<(), git2::Error> {
+		self.set_scope(LogScope::Lines(history))
+	}
+
+	pub fn start_pickaxe(&mut self, pickaxe: Pickaxe) -> Result<(), Box<dyn Error>> {
+		// the loaded commits already have their patches, so only the rest of the log has to be searched.
+		// a patch that was switched to another parent or other options has to be diffed again, like git log would
+		let mut found = vec![];
+		let mut resume_after = None;
+		if self.state.scope.is_none() {
+			for ci in &self.state.commit_infos {
+				let matched = if ci.diff_mode == DiffMode::Parent(0) && ci.patch_options == PatchOptions::default() {
+					pickaxe.matches(&ci.patch)?
+				} else {
+					pickaxe.matches_commit(self.repo, &self.repo.find_commit(ci.commit_id)?)?
+				};
+				if matched {
+					found.push(ci.commit_id);
+				}
+			}
+			resume_after = self.state.commit_infos.last().map(|ci| ci.commit_id);
+		}
+		let search = PickaxeSearch::start(
+			self.repo.path().to_owned(),
+			self.revision_range.clone(),
+			self.state.filter.clone(),
+			pickaxe,
+			found,
+			resume_after,
+		);
+		self.set_scope(LogScope::Pickaxe(search))?;
+		Ok(())
+	}
+
+	// a running search has to start over to apply the new filter
+	fn filter_changed(&mut self) -> Result<(), Box<dyn Error>> {
+		if let Some(LogScope::Pickaxe(ref search)) = self.state.scope {
+			let pickaxe = search.pickaxe.clone();
+			self.state.scope = None;
+			self.state.commit_infos.clear();
+			return self.start_pickaxe(pickaxe);
+		}
+		Ok(self.reload_log()?)
+	}
+
+	fn searching(&self) -> bool {
+		self.state.scope.as_ref().is_some_and(LogScope::searching)
+	}
+
+	fn set_scope(&mut self, scope: LogScope) -> Result<(), git2::Error> {
+		self.state.scope = Some(scope);
+		self.state.commit_view = None;
+		self.history.clear();
+		self.forward.clear();
+		self.reload_log()
+	}
+
+	// loads the log up to the commit, unless it isn't in the log or a search hasn't found it yet
+	fn load_until(&mut self, commit_id: Oid) -> Result<Option<usize>, git2::Error> {
+		if let Some(index) = self.state.commit_infos.iter().position(|ci| ci.commit_id == commit_id) {
+			return Ok(Some(index));
+		}
+		// the scopes only narrow the log down, so this rules out walking all of a scope for a commit outside the log
+		if log_position(self.repo, &self.revision_range, &self.state.filter, commit_id)?.is_none() {
+			return Ok(None);
+		}
+		loop {
+			let loaded = self.state.commit_infos.len();
+			self.load_commits(loaded + 1);
+			match self.state.commit_infos.get(loaded) {
+				None => return Ok(None), // the end of the scope, or as far as a search has got
+				Some(ci) if ci.commit_id == commit_id => return Ok(Some(loaded)),
+				Some(_) => {},
+			}
+		}
+	}
+
+	// shows a commit from the log, loading the log up to it, or else on its own
+	fn go_to_commit(&mut self, commit_id: Oid) -> Result<(), git2::Error> {
+		match self.load_until(commit_id)? {
+			Some(index) => {
+				self.state.log_state.select(Some(index));
+				self.show_commit(index);
+			},
+			None => {
+				let commit = commit_info(self.repo, commit_id)?;
+				self.view_commit(ViewedCommit::Other(Box::new(commit)));
+			},
+		}
+		Ok(())
+	}
+
+	// follows a parent or child so that Action::Back can return
+	fn navigate(&mut self, commit_id: Oid) -> Result<(), git2::Error> {
+		if let Some(current) = self.selected_commit() {
+			self.history.push(current);
+		}
+		self.forward.clear();
+		self.go_to_commit(commit_id)
+	}
+
+	// selects the commit in the log, loading the scoped log up to it, or else opens it on its own
+	fn jump(&mut self, commit_id: Oid) -> Result<(), git2::Error> {
+		if self.state.commit_view.is_some() {
+			return self.navigate(commit_id);
+		}
+		match self.load_until(commit_id)? {
+			Some(index) => self.state.log_state.select(Some(index)),
+			None => {
+				let commit = commit_info(self.repo, commit_id)?;
+				self.view_commit(ViewedCommit::Other(Box::new(commit)));
+			},
+		}
+		Ok(())
+	}
+
+	fn selected_commit(&self) -> Option<Oid> {
+		match self.state.commit_view {
+			Some(ref show_commit) => Some(show_commit.commit.get(&self.state.commit_infos).commit_id),
+			None => match self.state.log_state.selected() {
+				Some(index) if index < self.state.commit_infos.len() => Some(self.state.commit_infos[index].commit_id),
+				_ => None,
+			},
+		}
+	}
+
+	fn toggle_mark(&mut self) {
+		if let Some(commit_id) = self.selected_commit() {
+			if !self.state.marked.remove(&commit_id) {
+				self.state.marked.insert(commit_id);
+			}
+			if self.state.commit_view.is_none() {
+				scroll(&mut self.state.log_state, 1, Some(self.state.commit_infos.len() - 1));
+			}
+		}
+	}
+
+	// the marked commits, oldest first, or else the selected commit
+	fn marked_or_selected(&self) -> Vec<Oid> {
+		if self.state.marked.is_empty() {
+			return self.selected_commit().into_iter().collect();
+		}
+		self.state
+			.commit_infos
+			.iter()
+			.rev()
+			.map(|ci| ci.commit_id)
+			.filter(|id| self.state.marked.contains(id))
+			.collect()
+	}
+
+	fn prompt(&mut self, kind: PromptKind, input: &str) {
+		self.state.prompt = Some(Prompt {
+			kind,
+			input: input.to_owned(),
+		});
+	}
+
+	fn submit_prompt(&mut self, kind: PromptKind, input: String) -> Result<(), Box<dyn Error>> {
+		match kind {
+			PromptKind::PatchDir => {
+				let patches = format_patches(self.repo, &self.marked_or_selected())?;
+				let dir = Path::new(&input);
+				fs::create_dir_all(dir)?;
+				for (name, contents) in &patches {
+					fs::write(dir.join(name), contents)?;
+				}
+				self.state.status = Some(format!("wrote {} patches to {}", patches.len(), dir.display()));
+			},
+			PromptKind::Jump => {
+				let commit = self.repo.revparse_single(input.trim())?.peel_to_commit()?;
+				self.jump(commit.id())?;
+			},
+			PromptKind::Filter(field) => {
+				self.state.filter.set(field, &input)?;
+				self.filter_changed()?;
+			},
+			PromptKind::Pickaxe => {
+				if !input.is_empty() {
+					self.start_pickaxe(Pickaxe::Count(input))?;
+				}
+			},
+			PromptKind::PickaxeRegex => {
+				if !input.is_empty() {
+					self.start_pickaxe(Pickaxe::Line(Regex::new(&input)?))?;
+				}
+			},
+			PromptKind::Search => {
+				let (width, height) = self.patch_size();
+				let Some(file_view) = self.state.commit_view.as_mut().and_then(|cv| cv.file_view.as_mut()) else {
+					return Ok(());
+				};
+				if input.is_empty() {
+					file_view.search = None;
+					return Ok(());
+				}
+				file_view.search(&input, width, height)?;
+				self.state.status =
+					Some(file_view.match_status().unwrap_or_else(|| format!("no matches for {}", input)));
+			},
+			PromptKind::FilterFiles => {
+				let Some(ref mut show_commit) = self.state.commit_view else {
+					return Ok(());
+				};
+				let shown = show_commit.files.current();
+				let index = show_commit.files.set_filter(input.trim().to_owned());
+				if let Some(index) = index.filter(|&index| Some(index) != shown) {
+					self.show_commit_file(index);
+				}
+			},
+			PromptKind::EditCopy => {
+				if !input.trim().eq_ignore_ascii_case("y") {
+					return Ok(());
+				}
+				let Some((path, blob_id, line)) = self.selected_file_line() else {
+					return Ok(());
+				};
+				let blob = self.repo.find_blob(blob_id)?;
+				let dir = private_temp_dir()?;
+				let copy = dir.join(path.file_name().unwrap_or(path.as_os_str()));
+				let res =
+					fs::write(&copy, blob.content()).map_err(Into::into).and_then(|_| self.run_editor(&copy, line));
+				_ = fs::remove_dir_all(&dir);
+				res?;
+			},
+			PromptKind::RenameThreshold | PromptKind::CopyThreshold => {
+				let threshold = match input.trim() {
+					"" => None,
+					percent => match percent.trim_end_matches('%').parse::<u16>() {
+						Ok(percent) if percent <= 100 => Some(percent),
+						_ => return Err(format!("not a percentage: {}", percent).into()),
+					},
+				};
+				if kind == PromptKind::RenameThreshold {
+					self.patch_options.renames = threshold;
+				} else {
+					self.patch_options.copies = threshold;
+				}
+				self.patch_options_changed()?;
+			},
+			PromptKind::Lines => {
+				let Some((commit_id, path)) = self.line_numbers_source() else {
+					return Ok(());
+				};
+				let spec = format!("{}:{}", input.trim(), path.to_string_lossy());
+				let history = line_history(self.repo, &commit_id.to_string(), &spec, self.state.theme.color)?;
+				self.show_line_history(history)?;
+			},
+		}
+		Ok(())
+	}
+
+	fn yank(&mut self, action: Action) -> Result<(), Box<dyn Error>> {
+		let (commit, file_index) = match self.state.commit_view {
+			Some(ref show_commit) => (
+				show_commit.commit.get(&self.state.commit_infos),
+				show_commit.files.current(),
+			),
+			None => match self.state.log_state.selected() {
+				Some(index) if index < self.state.commit_infos.len() => (&self.state.commit_infos[index], None),
+				_ => return Ok(()),
+			},
+		};
+		let text = match action {
+			Action::YankId => commit.commit_id.to_string(),
+			Action::YankShortId => {
+				let short_id = self.repo.find_object(commit.commit_id, None)?.short_id()?;
+				short_id.as_str().unwrap_or_default().to_owned()
+			},
+			Action::YankSummary => commit.summary.clone(),
+			Action::YankMessage => commit.message.clone(),
+			Action::YankPatch => patch_text(&commit.patch, file_index)?,
+			Action::YankEmail => {
+				let patches = format_patches(self.repo, &self.marked_or_selected())?;
+				let emails: Vec<String> =
+					patches.iter().map(|(_, email)| String::from_utf8_lossy(email).into_owned()).collect();
+				emails.concat()
+			},
+			_ => return Ok(()),
+		};
+		self.clipboard.copy(&text, self.term.backend_mut())?;
+
+		let first_line = text.lines().next().unwrap_or_default();
+		let mut status = format!("copied {}", first_line.chars().take(60).collect::<String>());
+		if text.lines().nth(1).is_some() || first_line.chars().count() > 60 {
+			status.push('…');
+		}
+		self.state.status = Some(status);
+		Ok(())
+	}
+
+	// the commit and path that the selected file's line numbers are for: the parent's in the file at parent view
+	fn line_numbers_source(&self) -> Option<(Oid, PathBuf)> {
+		let show_commit = self.state.commit_view.as_ref()?;
+		let commit = show_commit.commit.get(&self.state.commit_infos);
+		let delta = commit.patch.get_delta(show_commit.files.current()?)?;
+		if show_commit.content == FileContent::Old {
+			let parent = match commit.diff_mode {
+				DiffMode::Parent(n) => n,
+				_ => 0,
+			};
+			if let Some(&parent_id) = commit.parent_ids.get(parent) {
+				return Some((parent_id, delta.old_file().path()?.to_owned()));
+			}
+		}
+		Some((commit.commit_id, delta.new_file().path()?.to_owned()))
+	}
+
+	// inside the borders of the patch pane, as of the last draw
+	fn patch_size(&self) -> (u16, u16) {
+		let patch = self.state.areas.patch;
+		(patch.width.saturating_sub(2), patch.height.saturating_sub(2))
+	}
+
+	// into the next or previous file at either end of this one, unless gil.hunks-cross-files is false
+	fn step_hunk(&mut self, forward: bool) {
+		let (width, _) = self.patch_size();
+		let show_commit = self.state.commit_view.as_mut().unwrap();
+		if let Some(ref mut file_view) = show_commit.file_view {
+			if file_view.step_hunk(forward, width) {
+				self.state.status = file_view.hunk_status(width);
+				return;
+			}
+		}
+		let next_file = match show_commit.files.current() {
+			_ if show_commit.whole => None, // already at the first or last hunk of the commit
+			Some(_) => show_commit.files.neighbour(forward),
+			None => None,
+		};
+		let Some(index) = next_file.filter(|_| self.hunks_cross_files) else {
+			self.state.status = Some(if forward { "last hunk" } else { "first hunk" }.to_owned());
+			return;
+		};
+		show_commit.files.select(index);
+		self.show_commit_file(index);
+		let show_commit = self.state.commit_view.as_mut().unwrap();
+		if let Some(ref mut file_view) = show_commit.file_view {
+			file_view.scroll_to_hunk(forward, width);
+			self.state.status = file_view.hunk_status(width);
 		}
 	}
 
 	fn show_commit_file(&mut self, index: usize) {
+		let (width, _) = self.patch_size();
 		let show_commit = self.state.commit_view.as_mut().unwrap();
-		show_commit.show_file(self.repo, &self.state.commit_infos, index);
+		show_commit.show_file(self.repo, &self.state.commit_infos, index, &self.state.theme, width);
 	}
 }
 
-impl CommitView {
-	fn show_file(&mut self, repo: &Repository, commit_infos: &[CommitInfo], index: usize) {
+impl<'repo> CommitView<'repo> {
+	// in the whole commit, scrolls to it instead
+	fn show_file(
+		&mut self,
+		repo: &Repository,
+		commit_infos: &[CommitInfo<'repo>],
+		index: usize,
+		theme: &Theme,
+		width: u16,
+	) {
+		self.files.set_shown(index);
+		if self.whole {
+			if self.file_view.is_none() {
+				self.file_view = Some(show_whole(repo, self.commit.get(commit_infos), theme.color));
+			}
+			self.file_view.as_mut().unwrap().scroll_to_file(index, width);
+			return;
+		}
 		self.file_view = None;
-		let commit = &commit_infos[self.index];
-		let delta = commit.patch.get_delta(index).unwrap();
+		let commit = self.commit.get(commit_infos);
+		if self.content != FileContent::Patch {
+			let parent = self.content == FileContent::Old;
+			self.file_view = Some(match file_at(repo, &commit.patch, index, parent) {
+				Ok(file) => full_file_view(file, parent, theme, width),
+				Err(err) => FileView::new(Text::raw(err.message().to_owned()), &[]),
+			});
+			return;
+		}
+		let Some(delta) = commit.patch.get_delta(index) else {
+			return;
+		};
 		if delta.status() != git2::Delta::Deleted {
-			if let Some(path) = commit.patch.get_delta(index).unwrap().new_file().path() {
-				self.file_view = Some(FileView {
-					contents: show(repo, commit.commit_id, path),
-					scroll: 0,
-				});
+			if let Some(path) = delta.new_file().path() {
+				let headers = hunk_headers(&commit.patch, index).unwrap_or_default();
+				self.file_view = Some(FileView::new(show(repo, commit, Some(path), theme.color), &headers));
 			}
 		}
 	}
 }
 
+// with a line number gutter, and the changed lines marked. starts at the first of them
+fn full_file_view(file: FileAt, parent: bool, theme: &Theme, width: u16) -> FileView {
+	let (contents, changed) = match file {
+		FileAt::Text { contents, changed } => (contents, changed),
+		FileAt::Binary => return FileView::new(Text::raw("binary file"), &[]),
+		FileAt::Missing if parent => return FileView::new(Text::raw("added in this commit"), &[]),
+		FileAt::Missing => return FileView::new(Text::raw("deleted in this commit"), &[]),
+	};
+	let (marker, changed_style) = if parent {
+		('-', theme.deletions)
+	} else {
+		('+', theme.insertions)
+	};
+	let gutter_width = contents.lines().count().to_string().len();
+	let mut lines = vec![];
+	let mut hunks = vec![];
+	for (index, line) in contents.lines().enumerate() {
+		let line_number = u32::try_from(index + 1).unwrap_or(u32::MAX);
+		let text = line.replace('\t', "    "); // like `git show --expand-tabs=4`
+		if changed.contains(&line_number) {
+			if !changed.contains(&(line_number - 1)) {
+				hunks.push(index);
+			}
+			lines.push(Line::from(vec![
+				Span::styled(format!("{:>gutter_width$} {} ", line_number, marker), changed_style),
+				Span::styled(text, changed_style),
+			]));
+		} else {
+			lines.push(Line::from(vec![
+				Span::styled(format!("{:>gutter_width$}   ", line_number), theme.line_number),
+				Span::raw(text),
+			]));
+		}
+	}
+	let mut file_view = FileView::new(Text::from(lines), &[]);
+	file_view.hunks = hunks;
+	file_view.full_file = true;
+	file_view.scroll_to_hunk(true, width);
+	file_view
+}
+
+fn show_whole(repo: &Repository, commit: &CommitInfo, color: bool) -> FileView {
+	let mut headers = vec![];
+	let mut paths = vec![];
+	for (index, delta) in commit.patch.deltas().enumerate() {
+		headers.extend(hunk_headers(&commit.patch, index).unwrap_or_default());
+		let new_path = delta.new_file().path().or(delta.old_file().path());
+		let old_path = delta.old_file().path().or(new_path);
+		let path_text = |path: Option<&Path>| path.map(|path| path.to_string_lossy().into_owned()).unwrap_or_default();
+		paths.push((path_text(old_path), path_text(new_path)));
+	}
+	FileView::new(show(repo, commit, None, color), &headers).with_files(&paths)
+}
+
 #[derive(PartialEq)]
-enum LogMode {
+pub enum LogMode {
 	Short,
 	Medium,
 	Long,
 }
 
-pub fn setup() -> Result<CrosstermTerm, Box<dyn Error>> {
+static ACTIVE: AtomicBool = AtomicBool::new(false); // raw mode and the alternate screen are on
+static ON_TTY: AtomicBool = AtomicBool::new(false);
+
+// restores the terminal when dropped, including while unwinding from a panic or returning early with an error
+pub struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+	fn drop(&mut self) {
+		restore();
+	}
+}
+
+// with `tty`, draws on /dev/tty instead of stdout so that stdout can be used for output
+pub fn setup(mouse: bool, tty: bool) -> Result<(CrosstermTerm, TerminalGuard), Box<dyn Error>> {
+	ON_TTY.store(tty, Ordering::SeqCst);
+	let mut out = terminal_writer()?;
+	let guard = TerminalGuard;
+	enter(&mut out, mouse)?;
+	let backend = CrosstermBackend::new(out);
+	Ok((Terminal::new(backend)?, guard))
+}
+
+fn enter(out: &mut impl Write, mouse: bool) -> io::Result<()> {
 	enable_raw_mode()?;
-	let mut stdout = io::stdout();
-	execute!(stdout, EnterAlternateScreen)?;
-	let backend = CrosstermBackend::new(stdout);
-	Ok(Terminal::new(backend)?)
+	ACTIVE.store(true, Ordering::SeqCst);
+	execute!(out, EnterAlternateScreen)?;
+	if mouse {
+		execute!(out, EnableMouseCapture)?;
+	}
+	Ok(())
+}
+
+// safe to call more than once and from the panic hook
+pub fn restore() {
+	if !ACTIVE.swap(false, Ordering::SeqCst) {
+		return;
+	}
+	_ = disable_raw_mode();
+	if let Ok(mut out) = terminal_writer() {
+		_ = execute!(out, DisableMouseCapture, LeaveAlternateScreen, Show);
+	}
+}
+
+fn terminal_writer() -> io::Result<Box<dyn Write>> {
+	if ON_TTY.load(Ordering::SeqCst) {
+		Ok(Box::new(OpenOptions::new().write(true).open("/dev/tty")?))
+	} else {
+		Ok(Box::new(io::stdout()))
+	}
 }
 
 // returns whether to continue running the app
@@ -186,124 +1427,352 @@ fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Size) -> Result<bool,
 		app.state.popup = None;
 		return Ok(true);
 	}
+	app.state.status = None;
+	if let Some(ref mut prompt) = app.state.prompt {
+		match key.code {
+			KeyCode::Enter => {
+				let prompt = app.state.prompt.take().unwrap();
+				app.submit_prompt(prompt.kind, prompt.input)?;
+			},
+			KeyCode::Esc => app.state.prompt = None,
+			KeyCode::Backspace => _ = prompt.input.pop(),
+			KeyCode::Char('u') if key.modifiers == KeyModifiers::CONTROL => prompt.input.clear(),
+			KeyCode::Char('w') if key.modifiers == KeyModifiers::CONTROL => {
+				// the word before the cursor and the spaces after it
+				let word = prompt.input.trim_end().rfind(' ').map_or(0, |i| i + 1);
+				prompt.input.truncate(word);
+			},
+			KeyCode::Char(c) if (key.modifiers - KeyModifiers::SHIFT).is_empty() => prompt.input.push(c),
+			_ => {}, // ignored
+		}
+		return Ok(true);
+	}
+	if app.yank_pending {
+		app.yank_pending = false;
+		if let Some(action) = app.keymap.yank.action(key) {
+			app.yank(action)?;
+		}
+		return Ok(true);
+	}
+	if app.filter_pending {
+		app.filter_pending = false;
+		let field = match app.keymap.filter.action(key) {
+			Some(Action::FilterAuthor) => FilterField::Author,
+			Some(Action::FilterCommitter) => FilterField::Committer,
+			Some(Action::FilterSince) => FilterField::Since,
+			Some(Action::FilterUntil) => FilterField::Until,
+			Some(Action::FilterGrep) => FilterField::Grep,
+			Some(Action::ClearFilter) => {
+				app.state.filter.clear();
+				app.filter_changed()?;
+				return Ok(true);
+			},
+			_ => return Ok(true),
+		};
+		let current = app.state.filter.get(field).to_owned();
+		app.prompt(PromptKind::Filter(field), &current);
+		return Ok(true);
+	}
+	if app.diff_pending {
+		app.diff_pending = false;
+		let options = &mut app.patch_options;
+		match app.keymap.diff.action(key) {
+			Some(Action::IgnoreAllSpace) => options.ignore_all_space = !options.ignore_all_space,
+			Some(Action::IgnoreSpaceChange) => options.ignore_space_change = !options.ignore_space_change,
+			Some(Action::IgnoreSpaceAtEol) => options.ignore_space_at_eol = !options.ignore_space_at_eol,
+			Some(Action::CopiesHarder) => options.copies_harder = !options.copies_harder,
+			Some(Action::ResetDiffOptions) => *options = PatchOptions::default(),
+			Some(Action::RenameThreshold) => {
+				let current = options.renames.map(|percent| percent.to_string()).unwrap_or_default();
+				app.prompt(PromptKind::RenameThreshold, &current);
+				return Ok(true);
+			},
+			Some(Action::CopyThreshold) => {
+				let current = options.copies.map(|percent| percent.to_string()).unwrap_or_default();
+				app.prompt(PromptKind::CopyThreshold, &current);
+				return Ok(true);
+			},
+			_ => return Ok(true),
+		}
+		app.patch_options_changed()?;
+		return Ok(true);
+	}
+	if app.pick {
+		// anything else falls through to the log and commit bindings
+		if let Some(Action::Pick) = app.keymap.pick.action(key) {
+			app.picked = Some(app.marked_or_selected());
+			return Ok(false);
+		}
+	}
 
 	if let Some(ref mut show_commit) = app.state.commit_view {
-		match key {
-			KeyEvent { code: Char('n'), .. } => {
-				let max = app.state.commit_infos[show_commit.index].num_files - 1;
-				let index = scroll(&mut show_commit.files_state, 1, Some(max));
-				app.show_commit_file(index);
-			},
-			KeyEvent { code: Char('p'), .. } => {
-				let max = app.state.commit_infos[show_commit.index].num_files - 1;
-				let index = scroll(&mut show_commit.files_state, -1, Some(max));
-				app.show_commit_file(index);
-			},
-			KeyEvent {
-				code: KeyCode::Down, ..
-			} => show_commit.message_scroll = show_commit.message_scroll.saturating_add(1),
-			KeyEvent { code: KeyCode::Up, .. } => {
-				show_commit.message_scroll = show_commit.message_scroll.saturating_sub(1)
-			},
-			KeyEvent { code: Char('j'), .. } => scroll_file(&mut show_commit.file_view, term_size, 1),
-			KeyEvent { code: Char('k'), .. } => scroll_file(&mut show_commit.file_view, term_size, -1),
-			KeyEvent { code: Char('d'), .. }
-			| KeyEvent {
-				code: KeyCode::PageDown,
-				..
-			} => {
+		if let KeyCode::Char(c @ '0'..='9') = key.code {
+			let unbound = app.keymap.commit.action(key).is_none();
+			if unbound && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+				let digit = c.to_digit(10).unwrap() as usize;
+				if digit > 0 || app.count.is_some() {
+					let count = app.count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+					app.count = Some(count);
+					app.state.status = Some(count.to_string());
+					return Ok(true);
+				}
+			}
+		}
+		let count = app.count.take();
+		let Some(action) = app.keymap.commit.action(key) else {
+			return Ok(true);
+		};
+		let commit = show_commit.commit.get(&app.state.commit_infos);
+		let log_index = show_commit.commit.log_index();
+		match action {
+			Action::NextFile | Action::PrevFile => {
+				if let Some(index) = show_commit.files.neighbour(action == Action::NextFile) {
+					show_commit.files.select(index);
+					app.show_commit_file(index);
+				}
+			},
+			Action::ToggleDir => show_commit.files.toggle_dir(),
+			Action::ToggleAllDirs => show_commit.files.toggle_all(),
+			Action::SortFiles => app.file_sort = show_commit.files.toggle_sort(),
+			Action::FilterFiles => {
+				let filter = show_commit.files.filter().to_owned();
+				app.prompt(PromptKind::FilterFiles, &filter);
+			},
+			Action::NextCommit => {
+				if let Some(index) = log_index.map(|i| i + 1).filter(|i| *i < app.state.commit_infos.len()) {
+					app.state.log_state.select(Some(index));
+					app.show_commit(index);
+				}
+			},
+			Action::PrevCommit => {
+				if let Some(index) = log_index.and_then(|i| i.checked_sub(1)) {
+					app.state.log_state.select(Some(index));
+					app.show_commit(index);
+				}
+			},
+			Action::Parent => {
+				let n = count.unwrap_or(1);
+				match commit.parent_ids.get(n - 1) {
+					Some(&parent_id) => app.navigate(parent_id)?,
+					None if commit.parent_ids.is_empty() => app.state.status = Some("root commit".to_owned()),
+					None => {
+						app.state.status = Some(format!("no parent {} of {}", n, commit.parent_ids.len()));
+					},
+				}
+			},
+			Action::Child => {
+				let n = count.unwrap_or(1);
+				let children = app.children.get(&commit.commit_id).map_or(&[][..], |children| children);
+				match children.get(n - 1) {
+					Some(&child_id) => app.navigate(child_id)?,
+					None if children.is_empty() => {
+						app.state.status = Some("no children among the commits loaded".to_owned());
+					},
+					None => app.state.status = Some(format!("no child {} of {}", n, children.len())),
+				}
+			},
+			Action::Back => {
+				if let Some(commit_id) = app.history.pop() {
+					app.forward.push(commit.commit_id);
+					app.go_to_commit(commit_id)?;
+				}
+			},
+			Action::Forward => {
+				if let Some(commit_id) = app.forward.pop() {
+					app.history.push(commit.commit_id);
+					app.go_to_commit(commit_id)?;
+				}
+			},
+			Action::CycleDiffMode => {
+				let message_scroll = show_commit.message_scroll;
+				let commit = show_commit.commit.get_mut(&mut app.state.commit_infos);
+				if commit.parent_ids.len() > 1 {
+					let mode = commit.diff_mode.next(commit.parent_ids.len());
+					set_diff(app.repo, commit, mode, &app.patch_options)?;
+					app.show_first_file();
+					app.state.commit_view.as_mut().unwrap().message_scroll = message_scroll;
+				}
+			},
+			Action::MessageDown => show_commit.message_scroll = show_commit.message_scroll.saturating_add(1),
+			Action::MessageUp => show_commit.message_scroll = show_commit.message_scroll.saturating_sub(1),
+			Action::LineDown => scroll_file(&mut show_commit.file_view, term_size, 1),
+			Action::LineUp => scroll_file(&mut show_commit.file_view, term_size, -1),
+			Action::HalfPageDown => {
 				scroll_file(
 					&mut show_commit.file_view,
 					term_size,
 					(term_size.height / 2).try_into().unwrap(),
 				);
 			},
-			KeyEvent { code: Char('u'), .. }
-			| KeyEvent {
-				code: KeyCode::PageUp, ..
-			} => {
+			Action::HalfPageUp => {
 				scroll_file(
 					&mut show_commit.file_view,
 					term_size,
 					-i16::try_from(term_size.height / 2).unwrap(),
 				);
 			},
-			KeyEvent { code: Char('h'), .. } => app.state.popup = Some(make_commit_help_text()),
-			KeyEvent {
-				code: Char('q') | KeyCode::Esc,
-				..
-			} => {
+			Action::MoreContext => {
+				let lines = u32::try_from(count.unwrap_or(1)).unwrap_or(u32::MAX);
+				app.patch_options.context_lines = app.patch_options.context_lines.saturating_add(lines);
+				app.patch_options_changed()?;
+			},
+			Action::LessContext => {
+				let lines = u32::try_from(count.unwrap_or(1)).unwrap_or(u32::MAX);
+				app.patch_options.context_lines = app.patch_options.context_lines.saturating_sub(lines);
+				app.patch_options_changed()?;
+			},
+			Action::DiffOptions => app.diff_pending = true,
+			Action::CycleFileView => {
+				// whole files are shown one at a time
+				app.file_content = show_commit.content.next();
+				app.whole_commit = false;
+				show_commit.content = app.file_content;
+				show_commit.whole = false;
+				show_commit.file_view = None;
+				if let Some(index) = show_commit.files.current() {
+					app.show_commit_file(index);
+				}
+				app.state.status = Some(app.file_content.description().unwrap_or("patch").to_owned());
+			},
+			Action::WholeCommit => {
+				// the whole commit is only shown as a patch
+				app.whole_commit = !app.whole_commit;
+				if app.whole_commit {
+					app.file_content = FileContent::Patch;
+					show_commit.content = FileContent::Patch;
+				}
+				let current = show_commit.files.current();
+				show_commit.whole = app.whole_commit;
+				show_commit.file_view = None;
+				if let Some(index) = current {
+					app.show_commit_file(index);
+				}
+			},
+			Action::NextHunk | Action::PrevHunk => {
+				for _ in 0..count.unwrap_or(1) {
+					app.step_hunk(action == Action::NextHunk);
+				}
+			},
+			Action::Search if show_commit.file_view.is_some() => app.prompt(PromptKind::Search, ""),
+			Action::NextMatch | Action::PrevMatch => {
+				let (width, height) = app.patch_size();
+				let show_commit = app.state.commit_view.as_mut().unwrap();
+				if let Some(ref mut file_view) = show_commit.file_view {
+					let amount = count.unwrap_or(1) as isize;
+					let amount = if action == Action::NextMatch { amount } else { -amount };
+					file_view.step_match(amount, width, height);
+					app.state.status = file_view.match_status();
+				}
+			},
+			Action::Help => app.state.popup = Some(app.keymap.help_text(&app.keymap.commit, app.pick)),
+			Action::Yank => app.yank_pending = true,
+			Action::FormatPatch => app.prompt(PromptKind::PatchDir, "."),
+			Action::Jump => app.prompt(PromptKind::Jump, ""),
+			Action::Suspend => app.suspend()?,
+			Action::Edit => app.edit_selected_file()?,
+			Action::FileHistory => {
+				let path = show_commit.files.current().and_then(|index| commit.patch.get_delta(index));
+				if let Some(path) = path.and_then(|delta| delta.new_file().path().map(Path::to_owned)) {
+					app.show_file_history(path)?;
+				}
+			},
+			Action::LineHistory => {
+				if let Some(index) = show_commit.files.current() {
+					let range = match show_commit.file_view.as_ref().and_then(FileView::selected_lines) {
+						Some((first, last)) => format!("{},{}", first, last),
+						None => {
+							let parent = show_commit.content == FileContent::Old;
+							first_hunk_range(&commit.patch, index, parent)?.unwrap_or_default()
+						},
+					};
+					app.prompt(PromptKind::Lines, &range);
+				}
+			},
+			Action::SelectLines => {
+				let (width, _) = app.patch_size();
+				let show_commit = app.state.commit_view.as_mut().unwrap();
+				match show_commit.file_view {
+					Some(ref mut file_view) if file_view.full_file => {
+						// a single line is the start of a range, so the next press selects up to the top line
+						let extend = file_view.selection.is_some_and(|(start, end)| start == end);
+						if let Some(line) = file_view.line_at(0, width) {
+							file_view.select_line(line, extend);
+						}
+						app.state.status = file_view.selection_status();
+					},
+					_ => app.state.status = Some("lines can only be selected in the whole file".to_owned()),
+				}
+			},
+			Action::Quit => {
 				if app.show_only {
 					return Ok(false);
 				}
-				app.state.commit_view = None;
+				app.close_commit_view();
 			},
 			_ => {}, // ignored
 		}
 		return Ok(true);
 	}
 
-	match key {
+	let Some(action) = app.keymap.log.action(key) else {
+		return Ok(true);
+	};
+	match action {
 		// scroll
-		KeyEvent {
-			code: Char('j') | KeyCode::Down,
-			..
-		} => {
+		Action::NextCommit => {
 			scroll(&mut app.state.log_state, 1, None);
 		},
-		KeyEvent {
-			code: Char('k') | KeyCode::Up,
-			..
-		} => {
+		Action::PrevCommit => {
 			scroll(&mut app.state.log_state, -1, None);
 		},
-		KeyEvent { code: Char('d'), .. }
-		| KeyEvent {
-			code: KeyCode::PageDown,
-			..
-		} => {
+		Action::HalfPageDown => {
 			scroll(
 				&mut app.state.log_state,
 				(term_size.height / 4).try_into().unwrap(),
 				None,
 			);
 		},
-		KeyEvent { code: Char('u'), .. }
-		| KeyEvent {
-			code: KeyCode::PageUp, ..
-		} => {
+		Action::HalfPageUp => {
 			scroll(
 				&mut app.state.log_state,
 				-i16::try_from(term_size.height / 4).unwrap(),
 				None,
 			);
 		},
-		KeyEvent { code: Char('g'), .. }
-		| KeyEvent {
-			code: KeyCode::Home, ..
-		} => {
+		Action::FirstCommit => {
 			app.state.log_state.select_first();
 		},
 		// other interactions
-		KeyEvent { code: Char('1'), .. } => {
+		Action::ShortLog => {
 			app.state.log_mode = LogMode::Short;
 		},
-		KeyEvent { code: Char('2'), .. } => {
+		Action::RegularLog => {
 			app.state.log_mode = LogMode::Medium;
 		},
-		KeyEvent { code: Char('3'), .. } => {
+		Action::StatLog => {
 			app.state.log_mode = LogMode::Long;
 		},
-		KeyEvent {
-			code: KeyCode::Enter, ..
-		} => {
+		Action::ShowCommit => {
 			if let Some(index) = app.state.log_state.selected() {
 				app.show_commit(index);
 			}
 		},
-		KeyEvent { code: Char('h'), .. } => app.state.popup = Some(make_log_help_text()),
-		KeyEvent { code: Char('x'), .. } => {
-			app.teardown();
+		Action::Help => app.state.popup = Some(app.keymap.help_text(&app.keymap.log, app.pick)),
+		Action::Yank => app.yank_pending = true,
+		Action::Mark => app.toggle_mark(),
+		Action::FormatPatch => app.prompt(PromptKind::PatchDir, "."),
+		Action::Jump => app.prompt(PromptKind::Jump, ""),
+		Action::Filter => app.filter_pending = true,
+		Action::Pickaxe => app.prompt(PromptKind::Pickaxe, ""),
+		Action::PickaxeRegex => app.prompt(PromptKind::PickaxeRegex, ""),
+		Action::Suspend => app.suspend()?,
+		Action::ExecGitLog => {
+			if app.pick {
+				// `git log` would write to the pipe that is waiting for the picked commits
+				app.state.status = Some("not available with --pick".to_owned());
+				return Ok(true);
+			}
+			restore();
 			let mut args = vec!["log", app.revision_range.as_str()];
 			match app.state.log_mode {
 				LogMode::Short => {
@@ -314,20 +1783,157 @@ fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Size) -> Result<bool,
 					args.push("--stat");
 				},
 			}
-			let err = Command::new("git").args(&args).exec();
+			let err = git_command(app.repo).args(&args).exec();
 			panic!("failed to run `git {}`:\n\t{}", args.join(" "), err);
 		},
-		KeyEvent {
-			code: Char('q') | KeyCode::Esc,
-			..
-		} => {
-			return Ok(false);
+		Action::Quit => {
+			if app.state.scope.is_none() {
+				return Ok(false);
+			}
+			// back to the whole log, where the selected commit was
+			let selected = app.selected_commit();
+			app.state.scope = None;
+			app.reload_log()?;
+			if let Some(commit_id) = selected {
+				app.jump(commit_id)?;
+			}
 		},
 		_ => {}, // ignored
 	};
 	Ok(true)
 }
 
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+const WHEEL_LINES: i16 = 3;
+
+fn handle_mouse(mouse: &MouseEvent, app: &mut App, term_size: &Size) {
+	let pos = Position::new(mouse.column, mouse.row);
+	let state = &mut app.state;
+	if state.popup.is_some() {
+		if let MouseEventKind::Down(_) = mouse.kind {
+			state.popup = None;
+		}
+		return;
+	}
+
+	match mouse.kind {
+		MouseEventKind::Down(MouseButton::Left) => {
+			let double_click = match state.mouse.last_click {
+				Some((time, last_pos)) => last_pos == pos && time.elapsed() < DOUBLE_CLICK,
+				None => false,
+			};
+			state.mouse.last_click = if double_click {
+				None
+			} else {
+				Some((Instant::now(), pos))
+			};
+
+			if let Some(ref mut show_commit) = state.commit_view {
+				let patch = state.areas.patch;
+				let on_split = match state.areas.cap_direction {
+					Direction::Horizontal => pos.x == patch.x && patch.contains(pos),
+					Direction::Vertical => pos.y == patch.y && patch.contains(pos),
+				};
+				if on_split {
+					state.mouse.dragging_split = true;
+				} else if state.areas.files.contains(pos) {
+					let row = show_commit.files.state.offset() + usize::from(pos.y - state.areas.files.y);
+					let shown = show_commit.files.current();
+					if let Some(index) = show_commit.files.click(row).filter(|&index| Some(index) != shown) {
+						app.show_commit_file(index);
+					}
+				} else if patch.inner(Margin::new(1, 1)).contains(pos) {
+					if let Some(ref mut file_view) = show_commit.file_view {
+						let extend = mouse.modifiers.contains(KeyModifiers::SHIFT);
+						if file_view.select_row(pos.y - patch.y - 1, patch.width - 2, extend) {
+							state.mouse.selecting_lines = true;
+							state.status = file_view.selection_status();
+						}
+					}
+				}
+			} else if state.areas.log.contains(pos) {
+				if let Some(index) = log_index_at(state, pos.y) {
+					state.log_state.select(Some(index));
+					if double_click {
+						app.show_commit(index);
+					}
+				}
+			}
+		},
+		MouseEventKind::Drag(MouseButton::Left) if state.mouse.dragging_split => {
+			let cap = state.areas.cap;
+			let percent = match state.areas.cap_direction {
+				Direction::Horizontal => (pos.x.saturating_sub(cap.x) as u32 * 100 / cap.width.max(1) as u32) as u16,
+				Direction::Vertical => (pos.y.saturating_sub(cap.y) as u32 * 100 / cap.height.max(1) as u32) as u16,
+			};
+			state.cap_split = Some(percent.clamp(10, 90));
+		},
+		MouseEventKind::Drag(MouseButton::Left) if state.mouse.selecting_lines => {
+			let patch = state.areas.patch.inner(Margin::new(1, 1));
+			if let Some(ref mut file_view) = state.commit_view.as_mut().and_then(|c| c.file_view.as_mut()) {
+				// dragging above or below the pane stops at its edge
+				let row = pos.y.clamp(patch.y, patch.bottom().saturating_sub(1)) - patch.y;
+				file_view.select_row(row, patch.width, true);
+				state.status = file_view.selection_status();
+			}
+		},
+		MouseEventKind::Up(_) => {
+			state.mouse.dragging_split = false;
+			state.mouse.selecting_lines = false;
+		},
+		MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+			let amount = if mouse.kind == MouseEventKind::ScrollDown {
+				WHEEL_LINES
+			} else {
+				-WHEEL_LINES
+			};
+			match state.commit_view {
+				Some(ref mut show_commit) => {
+					if state.areas.patch.contains(pos) {
+						scroll_file(&mut show_commit.file_view, term_size, amount);
+					} else if state.areas.message.contains(pos) {
+						show_commit.message_scroll = show_commit.message_scroll.saturating_add_signed(amount);
+					} else if state.areas.files.contains(pos) {
+						if let Some(index) = show_commit.files.neighbour(amount > 0) {
+							show_commit.files.select(index);
+							app.show_commit_file(index);
+						}
+					}
+				},
+				None => {
+					scroll(&mut state.log_state, amount, None);
+				},
+			}
+		},
+		_ => {}, // ignored
+	}
+}
+
+// maps a screen row in the log to the index of the commit drawn there
+fn log_index_at(state: &AppRenderState, row: u16) -> Option<usize> {
+	let area = state.areas.log;
+	let mut y = area.y;
+	for (index, ci) in state.commit_infos.iter().enumerate().skip(state.log_state.offset()) {
+		let marked = state.marked.contains(&ci.commit_id);
+		let lines = commit_info_lines(
+			ci,
+			&state.log_mode,
+			&state.decorations,
+			&state.theme,
+			marked,
+			area.width,
+		);
+		y = y.saturating_add(u16::try_from(lines.len()).unwrap_or(u16::MAX));
+		if row < y {
+			return Some(index);
+		}
+		if y >= area.bottom() {
+			break;
+		}
+	}
+	None
+}
+
 fn scroll(list_state: &mut ListState, amount: i16, max: Option<usize>) -> usize {
 	let index = match list_state.selected() {
 		None => 0,
@@ -349,45 +1955,6 @@ fn scroll_file(show_file_option: &mut Option<FileView>, term_size: &Size, amount
 	}
 }
 
-fn make_log_help_text() -> Text<'static> {
-	let mut help = vec![
-		"h           this help",
-		"q  esc      close window",
-		"",
-		"1           short log",
-		"2           regular log",
-		"3           log with stat",
-		"",
-		"j  ↓        next commit",
-		"k  ↑        previous commit",
-		"d  pgdown   down half a window",
-		"u  pgup     up half a window",
-		"g  home     first commit",
-		"",
-		"enter       show commit",
-		"x           exec git log",
-	];
-	(help.drain(..).map(Line::from).collect::<Vec<_>>()).into()
-}
-fn make_commit_help_text() -> Text<'static> {
-	let mut help = vec![
-		"h           this help",
-		"q  esc      close window",
-		"",
-		"n           next file",
-		"p           previous file",
-		"",
-		"j           down one line",
-		"k           up one line",
-		"d  pgdown   down half a window",
-		"u  pgup     up half a window",
-		"",
-		"↓           scroll commit message down",
-		"↑           scroll commit message up",
-	];
-	(help.drain(..).map(Line::from).collect::<Vec<_>>()).into()
-}
-
 fn ui(frame: &mut Frame, state: &mut AppRenderState) {
 	let area = Rect::new(
 		frame.area().x,
@@ -396,32 +1963,63 @@ fn ui(frame: &mut Frame, state: &mut AppRenderState) {
 		frame.area().height - 1,
 	);
 
-	let highlight_style = Style::default().bg(Color::Indexed(237)); // 232 is black, 255 is white; 237 is dark gray
 	match state.commit_view {
 		None => {
 			// log view
-			let commit_list = List::new(
-				state
-					.commit_infos
-					.iter()
-					.map(|ci| commit_info_to_item(ci, &state.log_mode, &state.decorations, area.width)),
-			)
-			.highlight_style(highlight_style)
+			let commit_list = List::new(state.commit_infos.iter().map(|ci| {
+				let marked = state.marked.contains(&ci.commit_id);
+				ListItem::new(commit_info_lines(
+					ci,
+					&state.log_mode,
+					&state.decorations,
+					&state.theme,
+					marked,
+					area.width,
+				))
+			}))
+			.highlight_style(state.theme.highlight)
 			.scroll_padding(5);
 			frame.render_stateful_widget(commit_list, area, &mut state.log_state);
+			state.areas.log = area;
 
-			let bottom_color = Color::Indexed(245);
+			let bottom_style = state.theme.bottom_bar;
 			let mut modes = vec![
-				"[1] short ".fg(bottom_color),
-				"[2] regular ".fg(bottom_color),
-				"[3] stat".fg(bottom_color),
+				Span::styled("[1] short ", bottom_style),
+				Span::styled("[2] regular ", bottom_style),
+				Span::styled("[3] stat", bottom_style),
 			];
-			match state.log_mode {
-				LogMode::Short => modes[0] = modes[0].clone().bold().white(),
-				LogMode::Medium => modes[1] = modes[1].clone().bold().white(),
-				LogMode::Long => modes[2] = modes[2].clone().bold().white(),
+			let active = match state.log_mode {
+				LogMode::Short => 0,
+				LogMode::Medium => 1,
+				LogMode::Long => 2,
+			};
+			modes[active] = modes[active].clone().style(state.theme.bottom_bar_active);
+			let mut bottom_line = Line::from(modes);
+			let scope = match state.scope {
+				Some(LogScope::File(ref history)) => Some(format!("  history of {}", history.path.to_string_lossy())),
+				Some(LogScope::Lines(ref history)) => Some(format!(
+					"  history of -L {}{}",
+					history.spec,
+					if history.done { "" } else { "…" }
+				)),
+				Some(LogScope::Pickaxe(ref search)) => Some(format!(
+					"  {} ({} commits searched{})",
+					search.pickaxe.description(),
+					search.searched,
+					if search.done { "" } else { "…" }
+				)),
+				None => None,
+			};
+			if let Some(scope) = scope {
+				bottom_line.push_span(Span::styled(scope, state.theme.bottom_bar_active));
+			}
+			let filter = state.filter.description();
+			if !filter.is_empty() {
+				bottom_line.push_span(Span::styled(format!("  {}", filter), state.theme.bottom_bar_active));
+			}
+			if let Some(status) = &state.status {
+				bottom_line.push_span(Span::raw(format!("  {}", status)));
 			}
-			let bottom_line = Line::from(modes);
 			let bottom_area = Rect::new(frame.area().x, frame.area().height - 1, frame.area().width, 1);
 			frame.render_widget(Clear, bottom_area);
 			frame.render_widget(Paragraph::new(bottom_line), bottom_area);
@@ -438,29 +2036,28 @@ fn ui(frame: &mut Frame, state: &mut AppRenderState) {
 				message_width = area.width / 2;
 			};
 
-			let commit = &state.commit_infos[show_commit.index];
+			let commit = show_commit.commit.get(&state.commit_infos);
+			let mut title = commit.commit_id.to_string();
+			if show_commit.commit.log_index().is_none() {
+				if state.scope.as_ref().is_some_and(LogScope::searching) {
+					title.push_str(" (not loaded)");
+				} else {
+					title.push_str(" (not in the log)");
+				}
+			}
 			let commit_message = Paragraph::new(commit.message.as_str())
-				.block(Block::bordered().title(commit.commit_id.to_string()).title_style(Style::new().yellow()))
+				.block(Block::bordered().title(title).title_style(state.theme.title))
 				.wrap(Wrap { trim: false });
 			let commit_message_height: u16 = commit_message.line_count(message_width).try_into().unwrap_or(u16::MAX);
 
-			let mut commit_file_items = vec![];
-			for delta in commit.patch.deltas() {
-				let mut filename = match delta.new_file().path() {
-					Some(file_path) => file_path.to_string_lossy(),
-					None => "".into(),
-				};
-				if delta.status() == git2::Delta::Renamed || delta.status() == git2::Delta::Copied {
-					if let Some(old_path) = delta.old_file().path() {
-						filename = format!("{} → {}", old_path.to_string_lossy(), filename).into();
-					}
-				}
-				commit_file_items.push(filename);
-			}
-			let num_files = u16::try_from(commit_file_items.len()).unwrap_or(u16::MAX);
-			let commit_files = List::new(commit_file_items).highlight_style(highlight_style);
+			let files_title = show_commit.files.title();
+			let num_files =
+				u16::try_from(show_commit.files.len() + usize::from(files_title.is_some())).unwrap_or(u16::MAX);
+			let commit_files = List::new(show_commit.files.lines(&state.theme)).highlight_style(state.theme.highlight);
 
-			let cap_constraints = if cap_direction == Direction::Horizontal {
+			let cap_constraints = if let Some(percent) = state.cap_split {
+				[Constraint::Percentage(percent), Constraint::Fill(1)]
+			} else if cap_direction == Direction::Horizontal {
 				[Constraint::Percentage(50), Constraint::Percentage(50)]
 			} else {
 				let cap_height = commit_message_height.saturating_add(num_files).saturating_add(2); // add 2 for visual padding
@@ -477,21 +2074,78 @@ fn ui(frame: &mut Frame, state: &mut AppRenderState) {
 			show_commit.message_scroll =
 				show_commit.message_scroll.min(commit_message_height.saturating_sub(message_and_files[0].height));
 			let commit_message = commit_message.scroll((show_commit.message_scroll, 0));
+			if let Some(ref mut file_view) = show_commit.file_view {
+				// the file list is a table of contents for the whole commit
+				if let Some(index) = file_view.current_file(commit_and_patch[1].width.saturating_sub(2)) {
+					show_commit.files.follow(index);
+				}
+			}
 			frame.render_widget(commit_message, message_and_files[0]);
-			frame.render_stateful_widget(commit_files, message_and_files[1], &mut show_commit.files_state);
+			let mut files_area = message_and_files[1];
+			if let Some(title) = files_title {
+				let title_area = Rect {
+					height: 1,
+					..files_area
+				};
+				frame.render_widget(
+					Paragraph::new(title).style(state.theme.title),
+					title_area.intersection(files_area),
+				);
+				files_area.y += 1;
+				files_area.height = files_area.height.saturating_sub(1);
+			}
+			frame.render_stateful_widget(commit_files, files_area, &mut show_commit.files.state);
+			state.areas.cap = area;
+			state.areas.cap_direction = cap_direction;
+			state.areas.message = message_and_files[0];
+			state.areas.files = files_area;
+			state.areas.patch = commit_and_patch[1];
 
+			let mut patch_block = Block::bordered();
+			let mut patch_titles = vec![];
+			if commit.parent_ids.len() > 1 {
+				patch_titles.push(commit.diff_mode.description(&commit.parent_ids));
+			}
+			if let Some(content) = show_commit.content.description() {
+				patch_titles.push(content.to_owned());
+			}
+			if commit.patch_options != PatchOptions::default() {
+				patch_titles.push(commit.patch_options.description());
+			}
+			if !patch_titles.is_empty() {
+				patch_block = patch_block.title(patch_titles.join("  "));
+			}
 			if let Some(show_file) = &mut show_commit.file_view {
+				if let Some(hunk) = show_file.hunk_status(commit_and_patch[1].width.saturating_sub(2)) {
+					patch_block = patch_block.title_top(Line::from(hunk).right_aligned());
+				}
 				frame.render_widget(
-					Paragraph::new(show_file.contents.clone())
+					Paragraph::new(show_file.highlighted(&state.theme))
 						.wrap(Wrap { trim: false })
 						.scroll((show_file.scroll, 0))
-						.block(Block::bordered()),
+						.block(patch_block),
 					commit_and_patch[1],
 				);
+			} else if !patch_titles.is_empty() {
+				frame.render_widget(patch_block, commit_and_patch[1]);
+			}
+
+			if let Some(status) = &state.status {
+				let bottom_area = Rect::new(frame.area().x, frame.area().height - 1, frame.area().width, 1);
+				frame.render_widget(Paragraph::new(status.as_str()), bottom_area);
 			}
 		},
 	}
 
+	if let Some(prompt) = &state.prompt {
+		let bottom_area = Rect::new(frame.area().x, frame.area().height - 1, frame.area().width, 1);
+		let label = prompt.kind.label();
+		frame.render_widget(Clear, bottom_area);
+		frame.render_widget(Paragraph::new(format!("{}{}", label, prompt.input)), bottom_area);
+		let cursor_x = bottom_area.x + u16::try_from(label.chars().count() + prompt.input.chars().count()).unwrap_or(0);
+		frame.set_cursor_position(Position::new(cursor_x.min(bottom_area.right() - 1), bottom_area.y));
+	}
+
 	if let Some(popup) = &state.popup {
 		let paragraph = Paragraph::new(popup.clone()).wrap(Wrap { trim: false });
 		let area = centered_rect(80, 80, frame.area());
@@ -507,46 +2161,61 @@ fn ui(frame: &mut Frame, state: &mut AppRenderState) {
 	}
 }
 
-fn commit_info_to_item<'a>(
+pub fn commit_info_lines<'a>(
 	ci: &'a CommitInfo,
 	log_mode: &LogMode,
 	decorations: &'a Decorations,
+	theme: &Theme,
+	marked: bool,
 	width: u16,
-) -> ListItem<'a> {
+) -> Vec<Line<'a>> {
 	let mut commit_id = ci.commit_id.to_string();
 	if log_mode != &LogMode::Long {
 		commit_id.truncate(8);
 	}
-	let mut first_line = vec![Span::from(commit_id).yellow(), " ".to_span(), ci.time.to_span().green()];
+	let mut first_line = vec![];
+	if marked {
+		first_line.push(Span::styled("✔ ", theme.marked));
+	}
+	first_line.extend([
+		Span::styled(commit_id, theme.commit_id),
+		" ".to_span(),
+		Span::styled(ci.time.as_str(), theme.time),
+	]);
 	if log_mode == &LogMode::Short || log_mode == &LogMode::Medium {
 		first_line.extend([
 			" ".to_span(),
-			ci.author_name.to_span().light_blue().bold(),
-			format!(" <{}>", ci.author_email).blue(),
+			Span::styled(ci.author_name.as_str(), theme.author),
+			Span::styled(format!(" <{}>", ci.author_email), theme.email),
 		]);
 	}
 	if let Some(branches) = decorations.branches.get(&ci.commit_id) {
 		for (branch_name, branch_type) in branches {
 			first_line.push(" ".to_span());
-			let color = match branch_type {
-				BranchType::Local => Color::LightGreen,
-				BranchType::Remote => Color::LightRed,
+			let style = match branch_type {
+				BranchType::Local => theme.local_branch,
+				BranchType::Remote => theme.remote_branch,
 			};
-			first_line.push(Span::from(branch_name).style(Style::default().fg(color)));
+			first_line.push(Span::styled(branch_name, style));
+			if *branch_type == BranchType::Local {
+				if let Some(worktree) = decorations.worktrees.get(branch_name) {
+					first_line.push(Span::styled(format!("[{}]", worktree), theme.worktree));
+				}
+			}
 		}
 	}
 	if let Some(tags) = decorations.tags.get(&ci.commit_id) {
 		for tag in tags {
 			first_line.push(" ".to_span());
-			first_line.push(tag.to_span().light_yellow());
+			first_line.push(Span::styled(tag, theme.tag));
 		}
 	}
 
 	let mut lines = vec![Line::from(first_line)];
 	if log_mode == &LogMode::Long {
 		lines.push(Line::from(vec![
-			ci.author_name.to_span().light_blue().bold(),
-			format!(" <{}>", ci.author_email).blue(),
+			Span::styled(ci.author_name.as_str(), theme.author),
+			Span::styled(format!(" <{}>", ci.author_email), theme.email),
 		]));
 		lines.push(Line::raw(""));
 	}
@@ -558,10 +2227,24 @@ fn commit_info_to_item<'a>(
 		},
 	}
 	if *log_mode == LogMode::Long {
-		lines.extend(ci.stats.iter().map(|sl: &Line| sl.to_owned()));
+		lines.extend(ci.stats.iter().map(|sl| format_stat_line(sl, theme)));
 		lines.push(Line::from(""));
 	}
-	return lines.into();
+	return lines;
+}
+
+fn format_stat_line(line: &str, theme: &Theme) -> Line<'static> {
+	if let Some((path, changes)) = line.split_once(" | ") {
+		if let Some((num_changes, sigils)) = changes.rsplit_once(' ') {
+			let (insertions, deletions) = sigils.split_once('-').unwrap_or((sigils, ""));
+			return Line::from(vec![
+				Span::from(format!("{} | {} ", path, num_changes)),
+				Span::styled(insertions.to_owned(), theme.insertions),
+				Span::styled(deletions.to_owned(), theme.deletions),
+			]);
+		}
+	}
+	Line::from(line.to_owned())
 }
 
 fn wrap_line(line: &str, width: u16) -> impl Iterator<Item = Line> {
@@ -595,3 +2278,66 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 		)
 		.split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// what delta prints with --hunk-header-style=raw: no +/- on the patch lines and a box around the headers
+	const DELTA_PATCH: &str = "\
+src/main.rs
+───────────
+──────────────────────────────┐
+@@ -1,3 +1,4 @@ fn main() { │
+──────────────────────────────┘
+fn main() {
+@@ not a header, just a line in the file
+	let x = 1;
+───────────────────┐
+@@ -10,2 +11,3 @@ │
+───────────────────┘
+}";
+
+	#[test]
+	fn hunk_headers_with_delta() {
+		let headers = ["@@ -1,3 +1,4 @@".to_owned(), "@@ -10,2 +11,3 @@".to_owned()];
+		let mut file_view = FileView::new(Text::raw(DELTA_PATCH), &headers);
+		assert_eq!(file_view.hunks, [3, 9]);
+		assert!(file_view.step_hunk(true, 80));
+		assert!(file_view.step_hunk(true, 80));
+		assert_eq!(file_view.scroll, 9);
+		assert!(!file_view.step_hunk(true, 80));
+	}
+
+	#[test]
+	fn hunk_headers_split_differently() {
+		// git's diff.algorithm or diff.indentHeuristic gave other hunks than libgit2
+		let headers = ["@@ -1,3 +1,4 @@".to_owned(), "@@ -9,3 +10,4 @@".to_owned()];
+		let file_view = FileView::new(Text::raw(DELTA_PATCH), &headers);
+		assert_eq!(file_view.hunks, [3, 9]);
+	}
+
+	#[test]
+	fn hunk_header_lines() {
+		assert_eq!(hunk_header_line("@@ -1,3 +1,4 @@"), Some(1));
+		assert_eq!(hunk_header_line("@@ -10 +12 @@ fn main() {"), Some(12));
+		assert_eq!(hunk_header_line("@@@ -1,2 -3,2 +5,3 @@@"), Some(5));
+		assert_eq!(hunk_header_line("@@ -1,3 +1,4"), None);
+		assert_eq!(hunk_header_line("@@ -1,3 +1,4 @@@"), None);
+		assert_eq!(hunk_header_line("@@@ -1,2 +1,3 @@@"), None);
+		assert_eq!(hunk_header_line("@@ -1,3 +,4 @@"), None);
+		assert_eq!(hunk_header_line("@@ not a header"), None);
+		assert_eq!(hunk_header_line("src/main.rs:1: fn main() {"), None);
+	}
+
+	#[test]
+	fn hunk_line_with_delta() {
+		let headers = ["@@ -9,3 +10,4 @@".to_owned()];
+		let mut file_view = FileView::new(Text::raw(DELTA_PATCH), &headers);
+		assert_eq!(file_view.hunk_line(80), Some(1));
+		file_view.scroll = 9;
+		assert_eq!(file_view.hunk_line(80), Some(11));
+		file_view.scroll = 11;
+		assert_eq!(file_view.hunk_line(80), Some(11));
+	}
+}
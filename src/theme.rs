@@ -0,0 +1,185 @@
+use std::{env, str::FromStr};
+
+use git2::Config;
+use tui::style::{Color, Modifier, Style};
+
+pub struct Theme {
+	pub color: bool, // false when NO_COLOR is set; also disables colored `git show` output
+	pub highlight: Style,
+	pub marked: Style,
+	pub bottom_bar: Style,
+	pub bottom_bar_active: Style,
+	pub title: Style,
+	pub commit_id: Style,
+	pub time: Style,
+	pub author: Style,
+	pub email: Style,
+	pub local_branch: Style,
+	pub remote_branch: Style,
+	pub tag: Style,
+	pub worktree: Style,
+	pub insertions: Style,
+	pub deletions: Style,
+	pub line_number: Style,
+	pub search_match: Style,
+	pub search_current: Style,
+}
+
+impl Theme {
+	pub fn dark() -> Theme {
+		Theme {
+			color: true,
+			highlight: Style::new().bg(Color::Indexed(237)), // 232 is black, 255 is white; 237 is dark gray
+			marked: Style::new().fg(Color::LightMagenta).add_modifier(Modifier::BOLD),
+			bottom_bar: Style::new().fg(Color::Indexed(245)),
+			bottom_bar_active: Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+			title: Style::new().fg(Color::Yellow),
+			commit_id: Style::new().fg(Color::Yellow),
+			time: Style::new().fg(Color::Green),
+			author: Style::new().fg(Color::LightBlue).add_modifier(Modifier::BOLD),
+			email: Style::new().fg(Color::Blue),
+			local_branch: Style::new().fg(Color::LightGreen),
+			remote_branch: Style::new().fg(Color::LightRed),
+			tag: Style::new().fg(Color::LightYellow),
+			worktree: Style::new().fg(Color::Cyan),
+			insertions: Style::new().fg(Color::Green),
+			deletions: Style::new().fg(Color::Red),
+			line_number: Style::new().fg(Color::Indexed(243)),
+			search_match: Style::new().fg(Color::Black).bg(Color::Yellow),
+			search_current: Style::new().fg(Color::Black).bg(Color::Indexed(208)), // orange
+		}
+	}
+
+	pub fn light() -> Theme {
+		Theme {
+			color: true,
+			highlight: Style::new().bg(Color::Indexed(253)), // 253 is light gray
+			marked: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+			bottom_bar: Style::new().fg(Color::Indexed(242)),
+			bottom_bar_active: Style::new().fg(Color::Black).add_modifier(Modifier::BOLD),
+			title: Style::new().fg(Color::Indexed(130)), // dark orange
+			commit_id: Style::new().fg(Color::Indexed(130)),
+			time: Style::new().fg(Color::Indexed(28)), // dark green
+			author: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+			email: Style::new().fg(Color::Indexed(25)),
+			local_branch: Style::new().fg(Color::Indexed(28)),
+			remote_branch: Style::new().fg(Color::Indexed(124)), // dark red
+			tag: Style::new().fg(Color::Indexed(90)),            // dark magenta
+			worktree: Style::new().fg(Color::Indexed(30)),       // dark cyan
+			insertions: Style::new().fg(Color::Indexed(28)),
+			deletions: Style::new().fg(Color::Indexed(124)),
+			line_number: Style::new().fg(Color::Indexed(245)),
+			search_match: Style::new().fg(Color::Black).bg(Color::Indexed(229)), // pale yellow
+			search_current: Style::new().fg(Color::Black).bg(Color::Indexed(214)),
+		}
+	}
+
+	pub fn high_contrast() -> Theme {
+		let bold = Style::new().add_modifier(Modifier::BOLD);
+		Theme {
+			color: true,
+			highlight: Style::new().add_modifier(Modifier::REVERSED),
+			marked: bold.fg(Color::LightMagenta),
+			bottom_bar: Style::new().fg(Color::White),
+			bottom_bar_active: bold.fg(Color::LightYellow).add_modifier(Modifier::UNDERLINED),
+			title: bold.fg(Color::LightYellow),
+			commit_id: bold.fg(Color::LightYellow),
+			time: bold.fg(Color::LightGreen),
+			author: bold.fg(Color::LightCyan),
+			email: Style::new().fg(Color::LightCyan),
+			local_branch: bold.fg(Color::LightGreen),
+			remote_branch: bold.fg(Color::LightRed),
+			tag: bold.fg(Color::LightMagenta),
+			worktree: bold.fg(Color::LightBlue),
+			insertions: bold.fg(Color::LightGreen),
+			deletions: bold.fg(Color::LightRed),
+			line_number: Style::new().fg(Color::White),
+			search_match: Style::new().add_modifier(Modifier::REVERSED),
+			search_current: bold.add_modifier(Modifier::REVERSED | Modifier::UNDERLINED),
+		}
+	}
+
+	// only uses modifiers so that the selection and active mode are still visible
+	pub fn no_color() -> Theme {
+		Theme {
+			color: false,
+			highlight: Style::new().add_modifier(Modifier::REVERSED),
+			marked: Style::new().add_modifier(Modifier::BOLD),
+			bottom_bar: Style::new(),
+			bottom_bar_active: Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+			title: Style::new(),
+			commit_id: Style::new(),
+			time: Style::new(),
+			author: Style::new().add_modifier(Modifier::BOLD),
+			email: Style::new(),
+			local_branch: Style::new(),
+			remote_branch: Style::new(),
+			tag: Style::new(),
+			worktree: Style::new(),
+			insertions: Style::new(),
+			deletions: Style::new(),
+			line_number: Style::new(),
+			search_match: Style::new().add_modifier(Modifier::REVERSED),
+			search_current: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD | Modifier::UNDERLINED),
+		}
+	}
+
+	// https://no-color.org/ takes precedence over `gil.theme` (dark, light or high-contrast).
+	// individual colors can be overridden with `gil.color.<name>`, e.g. `gil.color.highlight = 254`
+	pub fn from_config(config: &Config) -> Result<Theme, String> {
+		if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+			return Ok(Theme::no_color());
+		}
+
+		let mut theme = match config.get_string("gil.theme") {
+			Ok(name) => match name.as_str() {
+				"dark" => Theme::dark(),
+				"light" => Theme::light(),
+				"high-contrast" => Theme::high_contrast(),
+				_ => return Err(format!("gil.theme: unknown theme {:?}", name)),
+			},
+			Err(err) if err.code() == git2::ErrorCode::NotFound => Theme::dark(),
+			Err(err) => return Err(err.message().to_owned()),
+		};
+
+		let mut entries = config.entries(Some("^gil\\.color\\.")).map_err(|e| e.message().to_owned())?;
+		while let Some(entry) = entries.next() {
+			let entry = entry.map_err(|e| e.message().to_owned())?;
+			let name = entry.name().unwrap_or_default();
+			let value = entry.value().unwrap_or_default();
+			let color = Color::from_str(value).map_err(|_| format!("{}: unknown color {:?}", name, value))?;
+			let style = match name.strip_prefix("gil.color.").unwrap_or_default() {
+				"highlight" => {
+					theme.highlight = theme.highlight.bg(color);
+					continue;
+				},
+				"search-match" => {
+					theme.search_match = theme.search_match.bg(color);
+					continue;
+				},
+				"search-current" => {
+					theme.search_current = theme.search_current.bg(color);
+					continue;
+				},
+				"marked" => &mut theme.marked,
+				"bottom-bar" => &mut theme.bottom_bar,
+				"bottom-bar-active" => &mut theme.bottom_bar_active,
+				"title" => &mut theme.title,
+				"commit-id" => &mut theme.commit_id,
+				"time" => &mut theme.time,
+				"author" => &mut theme.author,
+				"email" => &mut theme.email,
+				"local-branch" => &mut theme.local_branch,
+				"remote-branch" => &mut theme.remote_branch,
+				"tag" => &mut theme.tag,
+				"worktree" => &mut theme.worktree,
+				"insertions" => &mut theme.insertions,
+				"deletions" => &mut theme.deletions,
+				"line-number" => &mut theme.line_number,
+				_ => return Err(format!("{}: unknown color name", name)),
+			};
+			*style = style.fg(color);
+		}
+		Ok(theme)
+	}
+}